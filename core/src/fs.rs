@@ -46,6 +46,12 @@ impl FileType {
 }
 
 /// File type (regular vs directory) and size of a file.
+///
+/// Two `Metadata` values are equal if both the file type and the size are equal.  littlefs does
+/// not track modification times, so equal metadata does not mean that the contents are equal.
+/// To detect modifications that don't change the size, store a modification counter or
+/// timestamp in a custom attribute and compare it in addition to the metadata, see
+/// [`Metadata::same_as`][].
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metadata {
@@ -77,6 +83,33 @@ impl Metadata {
     pub fn is_empty(&self) -> bool {
         self.size == 0
     }
+
+    /// Returns `true` if `self` and `other` have the same type and size and the same
+    /// modification attribute.
+    ///
+    /// `modified` and `other_modified` are the values of a custom attribute that the caller
+    /// updates on every modification, for example a counter or timestamp, or `None` if the
+    /// attribute is not used or not set.  They are considered equal if both are `None` or if both
+    /// have the same contents.
+    ///
+    /// ```
+    /// # use littlefs2_core::{FileType, Metadata};
+    /// let cached = Metadata::new(FileType::File, 4);
+    /// let current = Metadata::new(FileType::File, 4);
+    /// assert!(current.same_as(Some(b"1"), &cached, Some(b"1")));
+    /// assert!(!current.same_as(Some(b"2"), &cached, Some(b"1")));
+    /// assert!(!current.same_as(Some(b"1"), &cached, None));
+    /// assert!(current.same_as(None, &cached, None));
+    /// assert!(!Metadata::new(FileType::File, 5).same_as(None, &cached, None));
+    /// ```
+    pub fn same_as(
+        &self,
+        modified: Option<&[u8]>,
+        other: &Metadata,
+        other_modified: Option<&[u8]>,
+    ) -> bool {
+        self == other && modified == other_modified
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -108,6 +141,15 @@ impl<'a> Attribute<'a> {
     pub fn total_size(&self) -> usize {
         self.total_size
     }
+
+    /// Returns `true` if the buffer used to read this attribute was smaller than the attribute.
+    ///
+    /// In this case, [`data`][`Attribute::data`] only contains the first bytes of the attribute.
+    /// To read the full attribute, query it again with a buffer of at least
+    /// [`total_size`][`Attribute::total_size`] bytes.
+    pub fn is_truncated(&self) -> bool {
+        self.data.len() < self.total_size
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
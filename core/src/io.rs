@@ -1,8 +1,12 @@
 //! Traits and types for core I/O functionality.
 
 use core::{
+    cell::{Cell, RefCell},
+    cmp,
     ffi::c_int,
-    fmt::{self, Debug, Formatter},
+    fmt::{self, Debug, Display, Formatter},
+    mem::ManuallyDrop,
+    ptr,
 };
 
 /// The `Read` trait allows for reading bytes from a file.
@@ -23,6 +27,190 @@ pub trait Read {
     }
 }
 
+impl<R: Read + ?Sized> Read for &R {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+
+/// Types that can be created from any byte sequence of their size.
+///
+/// This is used by `File::read_struct` in `littlefs2` to read fixed-layout records.  It is
+/// implemented for the primitive integer types and for arrays of `FromBytes` types.  The bytes
+/// are reinterpreted as they are, so multi-byte integers are read in the native byte order of
+/// the target.  Use fixed-size byte arrays and `from_le_bytes` or `from_be_bytes` for data that
+/// is exchanged between targets.
+///
+/// # Safety
+///
+/// Every sequence of `size_of::<Self>()` bytes must be a valid value of `Self`.  For structs,
+/// this requires `#[repr(C)]` or `#[repr(transparent)]` and fields that all implement
+/// `FromBytes`.  Types with padding are allowed, the padding is filled with the corresponding
+/// bytes of the input.
+pub unsafe trait FromBytes: Sized {}
+
+macro_rules! impl_from_bytes {
+    ($($ty:ty),*) => {
+        $(unsafe impl FromBytes for $ty {})*
+    };
+}
+
+impl_from_bytes!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+unsafe impl<T: FromBytes, const N: usize> FromBytes for [T; N] {}
+
+/// Adds a read-ahead buffer to a reader.
+///
+/// Small reads, for example when parsing a file byte by byte or line by line, are served from
+/// an internal buffer of `N` bytes that is refilled with a single call to the inner reader when
+/// it runs empty.  Reads that are at least as large as the buffer bypass it.  The buffer is
+/// stored inline, so a `BufReader` uses `N` bytes of RAM in addition to the inner reader.
+///
+/// As the buffer may contain data that has been read from the inner reader but not yet
+/// returned, the position of the inner reader is generally ahead of the position of the
+/// `BufReader`.
+pub struct BufReader<R, const N: usize> {
+    inner: R,
+    buf: RefCell<[u8; N]>,
+    pos: Cell<usize>,
+    filled: Cell<usize>,
+}
+
+impl<R: Read, const N: usize> BufReader<R, N> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: RefCell::new([0; N]),
+            pos: Cell::new(0),
+            filled: Cell::new(0),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the inner reader, discarding any buffered data.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Number of bytes that have been read from the inner reader but not yet returned.
+    pub fn buffered(&self) -> usize {
+        self.filled.get() - self.pos.get()
+    }
+
+    /// Discards the buffered data.
+    ///
+    /// This has to be called if the position of the inner reader is changed, for example by
+    /// seeking.
+    pub fn discard_buffer(&self) {
+        self.pos.set(0);
+        self.filled.set(0);
+    }
+
+    fn fill_buf(&self) -> Result<usize> {
+        if self.buffered() == 0 {
+            let n = self.inner.read(&mut *self.buf.borrow_mut())?;
+            self.pos.set(0);
+            self.filled.set(n);
+        }
+        Ok(self.buffered())
+    }
+
+    /// Reads bytes into `buf` until the delimiter `delim` is found, `buf` is full or the end of
+    /// the input is reached.
+    ///
+    /// The delimiter is included in the output.  Returns the number of bytes written to `buf`.
+    /// If the return value is non-zero and the last byte is not `delim`, either `buf` was too
+    /// small or the end of the input was reached.
+    pub fn read_until(&self, delim: u8, buf: &mut [u8]) -> Result<usize> {
+        let mut written = 0;
+        while written < buf.len() {
+            if self.fill_buf()? == 0 {
+                break;
+            }
+            let inner = self.buf.borrow();
+            let available = &inner[self.pos.get()..self.filled.get()];
+            let n = cmp::min(available.len(), buf.len() - written);
+            let (n, found) = match available[..n].iter().position(|b| *b == delim) {
+                Some(i) => (i + 1, true),
+                None => (n, false),
+            };
+            buf[written..][..n].copy_from_slice(&available[..n]);
+            self.pos.set(self.pos.get() + n);
+            written += n;
+            if found {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
+    /// Reads a line into `buf`, see [`BufReader::read_until`][].
+    pub fn read_line(&self, buf: &mut [u8]) -> Result<usize> {
+        self.read_until(b'\n', buf)
+    }
+}
+
+impl<R: Read, const N: usize> BufReader<R, N> {
+    /// Returns an iterator over the bytes of the reader.
+    ///
+    /// The bytes are read from the buffer, so the inner reader is only called once every `N`
+    /// bytes.  If the inner reader returns an error, the error is yielded and the iteration
+    /// ends.
+    pub fn bytes(self) -> Bytes<R, N> {
+        Bytes {
+            reader: self,
+            done: false,
+        }
+    }
+}
+
+/// An iterator over the bytes of a reader.
+///
+/// See [`BufReader::bytes`][].
+pub struct Bytes<R, const N: usize> {
+    reader: BufReader<R, N>,
+    done: bool,
+}
+
+impl<R: Read, const N: usize> Iterator for Bytes<R, N> {
+    type Item = Result<u8>;
+
+    fn next(&mut self) -> Option<Result<u8>> {
+        if self.done {
+            return None;
+        }
+        let mut byte = [0];
+        match self.reader.read(&mut byte) {
+            Ok(0) => {
+                self.done = true;
+                None
+            }
+            Ok(_) => Some(Ok(byte[0])),
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl<R: Read, const N: usize> Read for BufReader<R, N> {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        if self.buffered() == 0 && buf.len() >= N {
+            return self.inner.read(buf);
+        }
+        let available = self.fill_buf()?;
+        let n = cmp::min(available, buf.len());
+        let pos = self.pos.get();
+        buf[..n].copy_from_slice(&self.buf.borrow()[pos..][..n]);
+        self.pos.set(pos + n);
+        Ok(n)
+    }
+}
+
 /** The `Write` trait allows for writing bytes to a file.
 
 By analogy with `std::io::Write`, we also define a `flush()`
@@ -54,6 +242,95 @@ pub trait Write {
     }
 }
 
+impl<W: Write + ?Sized> Write for &W {
+    fn write(&self, data: &[u8]) -> Result<usize> {
+        (**self).write(data)
+    }
+
+    fn flush(&self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// Batches small writes to a writer.
+///
+/// Writes are collected in an internal buffer of `N` bytes and passed on to the inner writer
+/// in a single call once the buffer is full, when [`Write::flush`][] is called or when the
+/// `BufWriter` is dropped.  Writes that are at least as large as the buffer bypass it.  The
+/// buffer is stored inline, so a `BufWriter` uses `N` bytes of RAM in addition to the inner
+/// writer.
+///
+/// Buffered data is lost if the device loses power or crashes before it has been flushed.
+/// Errors that occur while flushing on drop are ignored, so call [`Write::flush`][] or
+/// [`BufWriter::into_inner`][] explicitly to handle them.
+pub struct BufWriter<W: Write, const N: usize> {
+    inner: W,
+    buf: RefCell<[u8; N]>,
+    len: Cell<usize>,
+}
+
+impl<W: Write, const N: usize> BufWriter<W, N> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: RefCell::new([0; N]),
+            len: Cell::new(0),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Number of bytes that have been written to the buffer but not yet to the inner writer.
+    pub fn buffered(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Writes the buffered data to the inner writer and returns it.
+    pub fn into_inner(self) -> Result<W> {
+        self.flush_buf()?;
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never dropped, so `inner` is only moved out once
+        Ok(unsafe { ptr::read(&this.inner) })
+    }
+
+    fn flush_buf(&self) -> Result<()> {
+        let len = self.len.get();
+        if len > 0 {
+            self.inner.write_all(&self.buf.borrow()[..len])?;
+            self.len.set(0);
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write, const N: usize> Write for BufWriter<W, N> {
+    fn write(&self, data: &[u8]) -> Result<usize> {
+        if self.len.get() + data.len() > N {
+            self.flush_buf()?;
+        }
+        if data.len() >= N {
+            return self.inner.write(data);
+        }
+        let len = self.len.get();
+        self.buf.borrow_mut()[len..][..data.len()].copy_from_slice(data);
+        self.len.set(len + data.len());
+        Ok(data.len())
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write, const N: usize> Drop for BufWriter<W, N> {
+    fn drop(&mut self) {
+        self.flush_buf().ok();
+    }
+}
+
 /** Enumeration of possible methods to seek within an I/O object.
 
 Use the [`Seek`](../io/trait.Seek.html) trait.
@@ -170,6 +447,12 @@ impl Error {
     /// Filename too long
     pub const FILENAME_TOO_LONG: Self = Self::new_const(-36);
 
+    /// File was not opened for writing.
+    ///
+    /// This error is not returned by littlefs but by the wrappers in `littlefs2` that check the
+    /// open flags before writing to a file.
+    pub const READ_ONLY: Self = Self::new_const(-30);
+
     /// Construct an `Error` from an error code.
     ///
     /// Return values that are greater or equals to zero represent success.  In this case, `None`
@@ -190,6 +473,16 @@ impl Error {
     }
 
     /// Return the error code of this error.
+    ///
+    /// This is the raw `lfs_error` value returned by littlefs (or by the storage driver).  Codes
+    /// that do not correspond to one of the associated constants are preserved as-is:
+    ///
+    /// ```
+    /// # use littlefs2_core::Error;
+    /// let error = Error::new(-1234).unwrap();
+    /// assert_eq!(error.code(), -1234);
+    /// assert_ne!(error, Error::IO);
+    /// ```
     pub const fn code(&self) -> c_int {
         self.code
     }
@@ -206,8 +499,172 @@ impl Debug for Error {
     }
 }
 
+/// Prints the raw error code, see [`Error::code`][].
+///
+/// ```
+/// # use littlefs2_core::Error;
+/// assert_eq!(format!("{}", Error::NO_SUCH_ENTRY), "littlefs error -2");
+/// ```
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "littlefs error {}", self.code)
+    }
+}
+
 impl From<Error> for c_int {
     fn from(error: Error) -> Self {
         error.code
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BufReader, BufWriter, Read, Result, Write};
+    use core::cell::{Cell, RefCell};
+
+    extern crate std;
+    use std::vec::Vec;
+
+    struct Bytes<'a> {
+        data: &'a [u8],
+        pos: Cell<usize>,
+        calls: Cell<usize>,
+    }
+
+    impl<'a> Bytes<'a> {
+        fn new(data: &'a [u8]) -> Self {
+            Self {
+                data,
+                pos: Cell::new(0),
+                calls: Cell::new(0),
+            }
+        }
+    }
+
+    impl Read for Bytes<'_> {
+        fn read(&self, buf: &mut [u8]) -> Result<usize> {
+            self.calls.set(self.calls.get() + 1);
+            let rest = &self.data[self.pos.get()..];
+            let n = rest.len().min(buf.len());
+            buf[..n].copy_from_slice(&rest[..n]);
+            self.pos.set(self.pos.get() + n);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn buf_reader() {
+        let inner = Bytes::new(b"hello world");
+        let reader = BufReader::<_, 4>::new(&inner);
+        let mut byte = [0; 1];
+        for expected in b"hello" {
+            assert_eq!(reader.read(&mut byte).unwrap(), 1);
+            assert_eq!(byte[0], *expected);
+        }
+        assert_eq!(inner.calls.get(), 2);
+        assert_eq!(reader.buffered(), 3);
+
+        // large reads bypass the buffer once it is empty
+        let mut buf = [0; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b" wo");
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"rld");
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    struct Sink {
+        data: RefCell<[u8; 32]>,
+        len: Cell<usize>,
+        calls: Cell<usize>,
+    }
+
+    impl Sink {
+        fn new() -> Self {
+            Self {
+                data: RefCell::new([0; 32]),
+                len: Cell::new(0),
+                calls: Cell::new(0),
+            }
+        }
+
+        fn written(&self) -> Vec<u8> {
+            self.data.borrow()[..self.len.get()].to_vec()
+        }
+    }
+
+    impl Write for Sink {
+        fn write(&self, data: &[u8]) -> Result<usize> {
+            self.calls.set(self.calls.get() + 1);
+            let len = self.len.get();
+            self.data.borrow_mut()[len..][..data.len()].copy_from_slice(data);
+            self.len.set(len + data.len());
+            Ok(data.len())
+        }
+
+        fn flush(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn buf_writer() {
+        let sink = Sink::new();
+        let writer = BufWriter::<_, 4>::new(&sink);
+        writer.write_all(b"ab").unwrap();
+        writer.write_all(b"c").unwrap();
+        assert_eq!(sink.calls.get(), 0);
+        assert_eq!(writer.buffered(), 3);
+
+        // does not fit, flushes the buffer first
+        writer.write_all(b"de").unwrap();
+        assert_eq!(sink.calls.get(), 1);
+        assert_eq!(sink.written(), b"abc");
+
+        // large writes bypass the buffer
+        writer.write_all(b"fghij").unwrap();
+        assert_eq!(sink.calls.get(), 3);
+        assert_eq!(sink.written(), b"abcdefghij");
+
+        writer.write_all(b"k").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(sink.written(), b"abcdefghijk");
+
+        writer.write_all(b"l").unwrap();
+        drop(writer);
+        assert_eq!(sink.written(), b"abcdefghijkl");
+    }
+
+    #[test]
+    fn bytes() {
+        let inner = Bytes::new(b"abcde");
+        let mut bytes = BufReader::<_, 2>::new(&inner).bytes();
+        for expected in b"abcde" {
+            assert_eq!(bytes.next().unwrap().unwrap(), *expected);
+        }
+        assert!(bytes.next().is_none());
+        assert!(bytes.next().is_none());
+        assert_eq!(inner.calls.get(), 4);
+    }
+
+    #[test]
+    fn read_line() {
+        let inner = Bytes::new(b"first line\nsecond\n\nlast");
+        let reader = BufReader::<_, 4>::new(&inner);
+        let mut line = [0; 32];
+        let n = reader.read_line(&mut line).unwrap();
+        assert_eq!(&line[..n], b"first line\n");
+        let n = reader.read_line(&mut line).unwrap();
+        assert_eq!(&line[..n], b"second\n");
+        let n = reader.read_line(&mut line).unwrap();
+        assert_eq!(&line[..n], b"\n");
+
+        // the output buffer is too small
+        let mut short = [0; 2];
+        assert_eq!(reader.read_line(&mut short).unwrap(), 2);
+        assert_eq!(&short, b"la");
+        let n = reader.read_line(&mut line).unwrap();
+        assert_eq!(&line[..n], b"st");
+        assert_eq!(reader.read_line(&mut line).unwrap(), 0);
+    }
+}
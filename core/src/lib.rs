@@ -12,9 +12,12 @@ mod object_safe;
 mod path;
 
 pub use fs::{Attribute, DirEntry, FileOpenFlags, FileType, Metadata};
-pub use io::{Error, OpenSeekFrom, Read, Result, Seek, SeekFrom, Write};
+pub use io::{
+    BufReader, BufWriter, Bytes, Error, FromBytes, OpenSeekFrom, Read, Result, Seek, SeekFrom,
+    Write,
+};
 pub use object_safe::{DirEntriesCallback, DynFile, DynFilesystem, FileCallback, Predicate, Vec};
-pub use path::{Ancestors, Iter, Path, PathBuf, PathError};
+pub use path::{Ancestors, Escaped, Iter, IterWithLast, Path, PathBuf, PathError, StrOrderedPath};
 
 /// Creates a path from a string without a trailing null.
 ///
@@ -66,6 +66,21 @@ impl<const N: usize> Vec for heapless08::Vec<u8, N> {
 
 /// Object-safe trait for files.
 ///
+/// `DynFile` has [`Read`][], [`Seek`][] and [`Write`][] as supertraits, so `&dyn DynFile` can be
+/// used as a type-erased file handle that supports all three, without knowing the storage type.
+/// As these traits take `&self`, a shared reference is sufficient:
+///
+/// ```
+/// # use littlefs2_core::{DynFile, Result, SeekFrom};
+/// fn overwrite_header(file: &dyn DynFile, header: &[u8]) -> Result<()> {
+///     let position = file.seek(SeekFrom::Current(0))?;
+///     file.seek(SeekFrom::Start(0))?;
+///     file.write_all(header)?;
+///     file.seek(SeekFrom::Start(position as u32))?;
+///     Ok(())
+/// }
+/// ```
+///
 /// The methods for opening files cannot be implemented in this trait.  Use these methods instead:
 /// - [`DynFilesystem::create_file_and_then`](trait.DynFilesystem.html#method.create_file_and_then)
 /// - [`DynFilesystem::open_file_and_then`](trait.DynFilesystem.html#method.open_file_and_then)
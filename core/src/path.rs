@@ -5,7 +5,7 @@ use core::{
     convert::TryFrom,
     ffi::{c_char, CStr},
     fmt,
-    iter::FusedIterator,
+    iter::{FusedIterator, Peekable},
     ops, ptr, slice, str,
 };
 
@@ -112,11 +112,56 @@ impl Path {
             Ordering::Equal => other.len().cmp(&this.len()),
         }
     }
+
+    /// Compare two paths by their number of components and then with [`Path::cmp_lfs`][].
+    ///
+    /// Sorting paths with this comparison puts all paths of one depth before the paths of the
+    /// next depth, so parents always come before their children.  [`Path::cmp_lfs`][] alone
+    /// ignores the depth:  it orders `/a/b` before `/a` because `/a` is a prefix of `/a/b`.
+    ///
+    /// ```
+    ///# use std::cmp::Ordering;
+    ///# use littlefs2_core::path;
+    /// assert_eq!(path!("/a/b").cmp_lfs(path!("/a")), Ordering::Less);
+    /// assert_eq!(path!("/a/b").cmp_tree(path!("/a")), Ordering::Greater);
+    /// assert_eq!(path!("/b").cmp_tree(path!("/a/b")), Ordering::Less);
+    /// assert_eq!(path!("/a/c").cmp_tree(path!("/a/b")), Ordering::Greater);
+    ///```
+    pub fn cmp_tree(&self, other: &Path) -> Ordering {
+        self.iter()
+            .count()
+            .cmp(&other.iter().count())
+            .then_with(|| self.cmp_lfs(other))
+    }
+
+    /// Compare two paths, ignoring a single trailing slash
+    ///
+    /// One trailing slash is removed from both paths before comparing them, unless the path is
+    /// the root directory `/`.  The comparison is otherwise strict, so `.` components or
+    /// repeated slashes are not normalized.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// assert!(path!("/a/b").eq_ignoring_trailing_slash(path!("/a/b/")));
+    /// assert!(path!("/a/b/").eq_ignoring_trailing_slash(path!("/a/b/")));
+    /// assert!(!path!("/a/b").eq_ignoring_trailing_slash(path!("/a/b//")));
+    /// assert!(!path!("/").eq_ignoring_trailing_slash(path!("")));
+    ///```
+    pub fn eq_ignoring_trailing_slash(&self, other: &Path) -> bool {
+        fn trim(path: &str) -> &str {
+            match path.strip_suffix('/') {
+                Some(trimmed) if !trimmed.is_empty() => trimmed,
+                _ => path,
+            }
+        }
+        trim(self.as_str()) == trim(other.as_str())
+    }
 }
 
 /// Iterator over the ancestors of a Path
 ///
 /// See documentation for [`Path::ancestors`][]
+#[derive(Clone)]
 pub struct Ancestors<'a> {
     path: &'a str,
 }
@@ -157,6 +202,7 @@ impl FusedIterator for Ancestors<'_> {}
 /// Iterator over the components of a Path
 ///
 /// See documentation for [`Path::iter`][]
+#[derive(Clone)]
 pub struct Iter<'a> {
     path: &'a str,
 }
@@ -183,6 +229,23 @@ impl Iterator for Iter<'_> {
     }
 }
 
+/// Iterator over the components of a Path, marking the last component
+///
+/// See documentation for [`Path::iter_with_last`][]
+#[derive(Clone)]
+pub struct IterWithLast<'a> {
+    iter: Peekable<Iter<'a>>,
+}
+
+impl Iterator for IterWithLast<'_> {
+    type Item = (PathBuf, bool);
+    fn next(&mut self) -> Option<(PathBuf, bool)> {
+        let component = self.iter.next()?;
+        let is_last = self.iter.peek().is_none();
+        Some((component, is_last))
+    }
+}
+
 impl Path {
     /// Return true if the path is empty
     ///
@@ -196,6 +259,77 @@ impl Path {
         self.inner.to_bytes().is_empty()
     }
 
+    /// Return true if the path is the current directory `.`
+    ///
+    /// ```rust
+    ///# use littlefs2_core::path;
+    /// assert!(path!(".").is_current_dir());
+    /// assert!(!path!("./").is_current_dir());
+    /// assert!(!path!("..").is_current_dir());
+    /// ```
+    pub const fn is_current_dir(&self) -> bool {
+        self.const_eq(path!("."))
+    }
+
+    /// Return true if the path is the parent directory `..`
+    ///
+    /// Together with [`Path::iter`][], this can be used to check the components of a path:
+    ///
+    /// ```rust
+    ///# use littlefs2_core::path;
+    /// assert!(path!("..").is_parent_dir());
+    /// assert!(!path!(".").is_parent_dir());
+    /// assert!(!path!("...").is_parent_dir());
+    /// assert!(path!("a/../b").iter().any(|component| component.is_parent_dir()));
+    /// ```
+    pub const fn is_parent_dir(&self) -> bool {
+        self.const_eq(path!(".."))
+    }
+
+    /// Return false if resolving the path would escape the directory it is relative to
+    ///
+    /// The path escapes its starting directory if a `..` component removes more components than
+    /// precede it.  Absolute paths are resolved relative to the root directory `/`.  Note that
+    /// [`Path::join`][] replaces the base path if the joined path is absolute, so untrusted
+    /// absolute paths must be rejected separately before joining.
+    ///
+    /// ```rust
+    ///# use littlefs2_core::path;
+    /// assert!(path!("a/../b").is_contained());
+    /// assert!(!path!("../etc").is_contained());
+    /// assert!(!path!("a/../../b").is_contained());
+    /// assert!(!path!("/../etc").is_contained());
+    /// ```
+    pub fn is_contained(&self) -> bool {
+        let mut depth = 0usize;
+        for component in self.as_str().split('/') {
+            match component {
+                "" | "." => {}
+                ".." => match depth.checked_sub(1) {
+                    Some(parent) => depth = parent,
+                    None => return false,
+                },
+                _ => depth += 1,
+            }
+        }
+        true
+    }
+
+    /// Return the length of the path in bytes, not including the trailing null
+    ///
+    /// As this is a `const fn`, it can be used to size buffers at compile time.
+    ///
+    /// ```rust
+    ///# use littlefs2_core::{path, Path};
+    /// const PREFIX: &Path = path!("/backup/");
+    /// let buffer = [0u8; PREFIX.len_const() + 16];
+    /// assert_eq!(buffer.len(), 24);
+    /// assert_eq!(path!("").len_const(), 0);
+    /// ```
+    pub const fn len_const(&self) -> usize {
+        self.inner.to_bytes().len()
+    }
+
     /// Get the name of the file this path points to if it points to one
     ///
     /// ```
@@ -242,7 +376,7 @@ impl Path {
     /// assert_eq!(&*ancestors.next().unwrap(), path!("/"));
     /// assert!(ancestors.next().is_none());
     /// ```
-    pub fn ancestors(&self) -> Ancestors {
+    pub fn ancestors(&self) -> Ancestors<'_> {
         Ancestors {
             path: self.as_str(),
         }
@@ -260,21 +394,70 @@ impl Path {
     /// assert_eq!(&*iter.next().unwrap(), path!("file.extension"));
     /// assert!(iter.next().is_none());
     /// ```
-    pub fn iter(&self) -> Iter {
+    pub fn iter(&self) -> Iter<'_> {
         Iter {
             path: self.as_str(),
         }
     }
 
+    /// Iterate over the components of the path together with a flag that is true for the last
+    /// component
+    ///
+    /// This is useful when the last component is handled differently, for example when creating
+    /// the parent directories of a file.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// let path = path!("/some/file.extension");
+    /// let mut iter = path.iter_with_last();
+    /// assert_eq!(iter.next(), Some((path!("/").into(), false)));
+    /// assert_eq!(iter.next(), Some((path!("some").into(), false)));
+    /// assert_eq!(iter.next(), Some((path!("file.extension").into(), true)));
+    /// assert!(iter.next().is_none());
+    /// ```
+    pub fn iter_with_last(&self) -> IterWithLast<'_> {
+        IterWithLast {
+            iter: self.iter().peekable(),
+        }
+    }
+
     /// Creates a path from a string.
     ///
     /// The string must only consist of ASCII characters.  The last character must be null.  It
     /// must contain at most [`PathBuf::MAX_SIZE`][] bytes, not including the trailing null.  If
     /// these conditions are not met, this function returns an error.
+    ///
+    /// This is the checked constructor for string slices:  it validates the string and returns
+    /// a `&Path` borrowing it, without copying.  To convert a string without a trailing null,
+    /// use [`PathBuf::try_from`][] instead, which copies the string into a buffer.
+    ///
+    /// ```
+    ///# use littlefs2_core::{path, Path, PathError};
+    /// assert_eq!(Path::from_str_with_nul("config/app.toml\0"), Ok(path!("config/app.toml")));
+    /// assert_eq!(Path::from_str_with_nul("config/app.toml"), Err(PathError::NotCStr));
+    /// assert_eq!(Path::from_str_with_nul("config/\0app.toml\0"), Err(PathError::NotCStr));
+    /// assert_eq!(Path::from_str_with_nul("caf\u{e9}\0"), Err(PathError::NotAscii));
+    /// ```
     pub const fn from_str_with_nul(s: &str) -> Result<&Self> {
         Self::from_bytes_with_nul(s.as_bytes())
     }
 
+    /// Creates a path from a string, validating it without copying.
+    ///
+    /// This is an alias for [`Path::from_str_with_nul`][]:  the string must only consist of ASCII
+    /// characters, end with its only null character and contain at most [`PathBuf::MAX_SIZE`][]
+    /// bytes, not including the trailing null.  Otherwise, an error is returned.
+    ///
+    /// ```
+    ///# use littlefs2_core::{path, Path, PathError};
+    /// assert_eq!(Path::from_str_checked("config/app.toml\0"), Ok(path!("config/app.toml")));
+    /// assert_eq!(Path::from_str_checked("config/app.toml"), Err(PathError::NotCStr));
+    /// assert_eq!(Path::from_str_checked("caf\u{e9}\0"), Err(PathError::NotAscii));
+    /// ```
+    pub const fn from_str_checked(s: &str) -> Result<&Self> {
+        Self::from_str_with_nul(s)
+    }
+
     /// Creates a path from a byte buffer.
     ///
     /// The byte buffer must only consist of ASCII characters.  The last character must be null.
@@ -319,13 +502,222 @@ impl Path {
         self.inner.as_ptr()
     }
 
+    /// Returns an object that displays the path with control characters escaped as `\xNN`.
+    ///
+    /// The [`Display`](fmt::Display) implementation of `Path` writes the path unchanged, so
+    /// control characters in file names, like tabs or escape sequences, are passed on to the
+    /// output.  Use this method when printing untrusted paths to a terminal.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// let path = path!("/some\tpath/file\x1b[2J");
+    /// assert_eq!(path.display_escaped().to_string(), "/some\\x09path/file\\x1b[2J");
+    /// ```
+    pub fn display_escaped(&self) -> Escaped<'_> {
+        Escaped {
+            path: self.as_str(),
+        }
+    }
+
     /// Creates an owned `PathBuf` with `path` adjoined to `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting path would be longer than [`PathBuf::MAX_SIZE`][].  Use
+    /// [`Path::try_join`][] for untrusted input.
     pub fn join(&self, path: &Path) -> PathBuf {
         let mut p = PathBuf::from(self);
         p.push(path);
         p
     }
 
+    /// Creates an owned `PathBuf` with `path` adjoined to `self`, or returns an error if the
+    /// result would be too long.
+    pub fn try_join(&self, path: &Path) -> Result<PathBuf> {
+        let mut p = PathBuf::from(self);
+        p.try_push(path)?;
+        Ok(p)
+    }
+
+    /// Creates an owned `PathBuf` with the relative path `rel` adjoined to `self`, making sure
+    /// that the result stays below `self`.
+    ///
+    /// Unlike [`Path::join`][], an absolute `rel` does not replace `self`, but is rejected.  If
+    /// `rel` is absolute or escapes `self` through `..` components, see [`Path::is_contained`][],
+    /// [`PathError::NotContained`][] is returned.  If the result would be too long,
+    /// [`PathError::TooLarge`][] is returned.  Use this method to join untrusted paths onto a
+    /// sandbox directory.
+    ///
+    /// ```
+    ///# use littlefs2_core::{path, PathError};
+    /// let root = path!("/sandbox");
+    /// assert_eq!(root.join_rooted(path!("a/../b")).unwrap(), path!("/sandbox/a/../b"));
+    /// assert_eq!(root.join_rooted(path!("/etc")), Err(PathError::NotContained));
+    /// assert_eq!(root.join_rooted(path!("a/../../etc")), Err(PathError::NotContained));
+    /// ```
+    pub fn join_rooted(&self, rel: &Path) -> Result<PathBuf> {
+        if rel.as_str().starts_with('/') || !rel.is_contained() {
+            return Err(PathError::NotContained);
+        }
+        self.try_join(rel)
+    }
+
+    /// Creates an owned `PathBuf` like `self` but with the file name replaced by `name`.
+    ///
+    /// If `self` is empty or ends with a slash, `name` is appended instead.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// let path = path!("/some/path/old.txt");
+    /// assert_eq!(&*path.with_file_name(path!("new.txt")), path!("/some/path/new.txt"));
+    /// assert_eq!(&*path!("old.txt").with_file_name(path!("new.txt")), path!("new.txt"));
+    /// assert_eq!(&*path!("/").with_file_name(path!("new.txt")), path!("/new.txt"));
+    /// assert_eq!(&*path!("/dir/").with_file_name(path!("new.txt")), path!("/dir/new.txt"));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting path would be longer than [`PathBuf::MAX_SIZE`][].  Use
+    /// [`Path::try_with_file_name`][] for untrusted input.
+    pub fn with_file_name(&self, name: &Path) -> PathBuf {
+        let mut path = self.without_file_name();
+        path.push(name);
+        path
+    }
+
+    /// Creates an owned `PathBuf` like `self` but with the file name replaced by `name`, or
+    /// returns an error if the result would be too long.
+    ///
+    /// ```
+    ///# use littlefs2_core::{path, PathBuf, PathError};
+    /// let path = path!("/some/path/old.txt");
+    /// assert_eq!(path.try_with_file_name(path!("new.txt")).unwrap(), path!("/some/path/new.txt"));
+    ///
+    /// let long = PathBuf::try_from([b'a'; PathBuf::MAX_SIZE].as_slice()).unwrap();
+    /// assert_eq!(path.try_with_file_name(&long), Err(PathError::TooLarge));
+    /// ```
+    pub fn try_with_file_name(&self, name: &Path) -> Result<PathBuf> {
+        let mut path = self.without_file_name();
+        path.try_push(name)?;
+        Ok(path)
+    }
+
+    fn without_file_name(&self) -> PathBuf {
+        let this = self.as_str();
+        if this.is_empty() || this.ends_with('/') {
+            return PathBuf::from(self);
+        }
+        match this.rsplit_once('/') {
+            Some(("", _)) => PathBuf::from(path!("/")),
+            Some((parent, _)) => PathBuf::try_from(parent).unwrap(),
+            None => PathBuf::new(),
+        }
+    }
+
+    /// Returns true if the extension of the last component equals `ext`.
+    ///
+    /// `ext` is given without the leading dot and compared case-sensitively.  The extension is
+    /// the part of the last component after the last dot.  If the last component has no dot,
+    /// only has a leading dot, or if the path ends with a slash, the path has no extension and
+    /// `false` is returned.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// assert!(path!("/firmware/image.bin").has_extension("bin"));
+    /// assert!(path!("image.tar.gz").has_extension("gz"));
+    /// assert!(!path!("image.tar.gz").has_extension("tar.gz"));
+    /// assert!(!path!("image.BIN").has_extension("bin"));
+    /// assert!(!path!("image").has_extension(""));
+    /// assert!(!path!(".bin").has_extension("bin"));
+    /// assert!(!path!("/dir.bin/").has_extension("bin"));
+    /// ```
+    pub fn has_extension(&self, ext: &str) -> bool {
+        self.extension() == Some(ext)
+    }
+
+    /// Returns true if the extension of the last component equals `ext`, ignoring ASCII case.
+    ///
+    /// See [`Path::has_extension`][].
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// assert!(path!("/firmware/image.BIN").has_extension_ignore_case("bin"));
+    /// assert!(!path!("/firmware/image").has_extension_ignore_case("bin"));
+    /// ```
+    pub fn has_extension_ignore_case(&self, ext: &str) -> bool {
+        matches!(self.extension(), Some(extension) if extension.eq_ignore_ascii_case(ext))
+    }
+
+    /// Returns true if the extension of the last component equals any of `exts`.
+    ///
+    /// See [`Path::has_extension`][] for how the extension is determined.  The extensions are
+    /// given without the leading dot and compared case-sensitively.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// let exts = ["bin", "hex"];
+    /// assert!(path!("/firmware/image.bin").ends_with_any_extension(&exts));
+    /// assert!(path!("/firmware/image.hex").ends_with_any_extension(&exts));
+    /// assert!(!path!("/firmware/image.HEX").ends_with_any_extension(&exts));
+    /// assert!(!path!("/firmware/image").ends_with_any_extension(&exts));
+    /// assert!(!path!("/firmware/").ends_with_any_extension(&exts));
+    /// ```
+    pub fn ends_with_any_extension(&self, exts: &[&str]) -> bool {
+        matches!(self.extension(), Some(extension) if exts.contains(&extension))
+    }
+
+    /// Returns true if the extension of the last component equals any of `exts`, ignoring ASCII
+    /// case.
+    ///
+    /// See [`Path::ends_with_any_extension`][].
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// assert!(path!("/firmware/image.HEX").ends_with_any_extension_ignore_case(&["bin", "hex"]));
+    /// ```
+    pub fn ends_with_any_extension_ignore_case(&self, exts: &[&str]) -> bool {
+        matches!(
+            self.extension(),
+            Some(extension) if exts.iter().any(|ext| extension.eq_ignore_ascii_case(ext))
+        )
+    }
+
+    /// Returns the extension of the last component including the leading dot.
+    ///
+    /// The extension is determined as for [`Path::has_extension`][]:  if the last component has
+    /// no dot, only has a leading dot like the hidden file `.bin`, or if the path ends with a
+    /// slash, `None` is returned.  A last component that ends with a dot has the extension `.`.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// assert_eq!(path!("/logs/app.log.gz").dotted_extension(), Some(path!(".gz")));
+    /// assert_eq!(path!("app.").dotted_extension(), Some(path!(".")));
+    /// assert_eq!(path!("app").dotted_extension(), None);
+    /// assert_eq!(path!(".bin").dotted_extension(), None);
+    /// assert_eq!(path!("/dir.d/").dotted_extension(), None);
+    /// ```
+    pub fn dotted_extension(&self) -> Option<&Path> {
+        let extension = self.extension()?;
+        let bytes = self.inner.to_bytes_with_nul();
+        let start = bytes.len() - 1 - extension.len() - 1;
+        debug_assert_eq!(bytes[start], b'.');
+        unsafe {
+            let cstr = CStr::from_bytes_with_nul_unchecked(&bytes[start..]);
+            Some(Path::from_cstr_unchecked(cstr))
+        }
+    }
+
+    fn extension(&self) -> Option<&str> {
+        let name = self.as_str().rsplit('/').next()?;
+        if name == ".." {
+            return None;
+        }
+        match name.rsplit_once('.') {
+            Some(("", _)) | None => None,
+            Some((_, extension)) => Some(extension),
+        }
+    }
+
     // helpful for debugging wither the trailing nul is indeed a trailing nul.
     pub const fn as_str_ref_with_trailing_nul(&self) -> &str {
         // SAFETY: ASCII is valid UTF-8
@@ -337,6 +729,26 @@ impl Path {
         unsafe { str::from_utf8_unchecked(self.inner.to_bytes()) }
     }
 
+    /// Returns the bytes of the path without the trailing nul.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// assert_eq!(path!("/a/b").bytes(), b"/a/b");
+    /// ```
+    pub const fn bytes(&self) -> &[u8] {
+        self.inner.to_bytes()
+    }
+
+    /// Returns the bytes of the path including the trailing nul.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// assert_eq!(path!("/a/b").bytes_with_nul(), b"/a/b\0");
+    /// ```
+    pub const fn bytes_with_nul(&self) -> &[u8] {
+        self.inner.to_bytes_with_nul()
+    }
+
     pub fn parent(&self) -> Option<PathBuf> {
         let rk_path_bytes = self.as_ref()[..].as_bytes();
         match rk_path_bytes.iter().rposition(|x| *x == b'/') {
@@ -355,6 +767,182 @@ impl Path {
             None => None,
         }
     }
+
+    /// Replaces the prefix `from_root` of `self` with `to_root`.
+    ///
+    /// The prefix is matched component-wise like in [`Path::strip_prefix`][].  Returns `None` if
+    /// `self` does not start with `from_root` or if the resulting path would be too long.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// let path = path!("/old/a/b");
+    /// assert_eq!(path.rebase(path!("/old"), path!("/new")).as_deref(), Some(path!("/new/a/b")));
+    /// assert_eq!(path.rebase(path!("/old/a/b"), path!("/new")).as_deref(), Some(path!("/new")));
+    /// assert_eq!(path.rebase(path!("/"), path!("/new")).as_deref(), Some(path!("/new/old/a/b")));
+    /// assert_eq!(path.rebase(path!("/ol"), path!("/new")), None);
+    /// ```
+    pub fn rebase(&self, from_root: &Path, to_root: &Path) -> Option<PathBuf> {
+        let rest = self.strip_prefix(from_root)?;
+        if rest.is_empty() {
+            Some(to_root.into())
+        } else {
+            to_root.try_join(rest).ok()
+        }
+    }
+
+    /// Returns a path that, when joined onto `base`, yields `self`.
+    ///
+    /// The prefix is matched component-wise, so `/ab` is not prefixed by `/a`.  The returned path
+    /// borrows from `self`.  If `base` is not a prefix of `self`, returns `None`.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// let path = path!("/some/path/file.extension");
+    /// assert_eq!(path.strip_prefix(path!("/")), Some(path!("some/path/file.extension")));
+    /// assert_eq!(path.strip_prefix(path!("/some")), Some(path!("path/file.extension")));
+    /// assert_eq!(path.strip_prefix(path!("/some/")), Some(path!("path/file.extension")));
+    /// assert_eq!(path.strip_prefix(path), Some(path!("")));
+    /// assert_eq!(path.strip_prefix(path!("/so")), None);
+    /// assert_eq!(path.strip_prefix(path!("some")), None);
+    /// ```
+    pub fn strip_prefix(&self, base: &Path) -> Option<&Path> {
+        let base = base.as_str();
+        if base.is_empty() {
+            return Some(self);
+        }
+        let this = self.as_str_ref_with_trailing_nul();
+        let rest = this.strip_prefix(base)?;
+        let rest = if base.ends_with('/') || rest == "\x00" {
+            rest
+        } else {
+            rest.strip_prefix('/')?
+        };
+        let rest = rest.trim_start_matches('/');
+        debug_assert!(rest.ends_with('\x00'));
+        unsafe {
+            let cstr = CStr::from_bytes_with_nul_unchecked(rest.as_bytes());
+            Some(Path::from_cstr_unchecked(cstr))
+        }
+    }
+
+    /// Splits a leading `scheme:` prefix from the path.
+    ///
+    /// The scheme is the part before the first `:`.  It must be non-empty and consist only of
+    /// ASCII letters and digits, so it cannot contain a `/` or a null byte.  Returns the scheme
+    /// without the colon and the remainder of the path, which borrows from `self`.  If the path
+    /// does not start with a valid scheme, returns `None`.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// assert_eq!(path!("flash:/config").split_scheme(), Some(("flash", path!("/config"))));
+    /// assert_eq!(path!("sd0:log.txt").split_scheme(), Some(("sd0", path!("log.txt"))));
+    /// assert_eq!(path!("ram:").split_scheme(), Some(("ram", path!(""))));
+    /// assert_eq!(path!("/config").split_scheme(), None);
+    /// assert_eq!(path!(":/config").split_scheme(), None);
+    /// assert_eq!(path!("dir/a:b").split_scheme(), None);
+    /// ```
+    pub fn split_scheme(&self) -> Option<(&str, &Path)> {
+        let this = self.as_str_ref_with_trailing_nul();
+        let (scheme, rest) = this.split_once(':')?;
+        if scheme.is_empty() || !scheme.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return None;
+        }
+        debug_assert!(rest.ends_with('\x00'));
+        let rest = unsafe {
+            let cstr = CStr::from_bytes_with_nul_unchecked(rest.as_bytes());
+            Path::from_cstr_unchecked(cstr)
+        };
+        Some((scheme, rest))
+    }
+
+    /// Splits the path into its first component and the remainder.
+    ///
+    /// For absolute paths, the first component is `/`.  The remainder borrows from `self`.  As
+    /// paths must be null terminated, the first component cannot borrow from `self` and is
+    /// returned as a `PathBuf`.  Returns `None` if the path is empty.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// let (first, rest) = path!("a/b/c").split_first().unwrap();
+    /// assert_eq!(&*first, path!("a"));
+    /// assert_eq!(rest, path!("b/c"));
+    ///
+    /// let (first, rest) = path!("/a/b").split_first().unwrap();
+    /// assert_eq!(&*first, path!("/"));
+    /// assert_eq!(rest, path!("a/b"));
+    ///
+    /// assert!(path!("").split_first().is_none());
+    /// ```
+    pub fn split_first(&self) -> Option<(PathBuf, &Path)> {
+        let first = self.iter().next()?;
+        self.strip_prefix(&first).map(|rest| (first, rest))
+    }
+
+    /// Returns the length in bytes of the prefix of `self` that contains the first `n`
+    /// components, without trailing slashes, or the length of `self` if it has fewer than `n`
+    /// components.
+    fn components_len(&self, n: usize) -> usize {
+        let bytes = self.as_str().as_bytes();
+        let mut count = 0;
+        let mut end = 0;
+        let mut i = 0;
+        if bytes.first() == Some(&b'/') {
+            if n == 0 {
+                return 0;
+            }
+            count = 1;
+            end = 1;
+            i = 1;
+        }
+        while i < bytes.len() {
+            if bytes[i] == b'/' {
+                i += 1;
+                continue;
+            }
+            if count == n {
+                break;
+            }
+            while i < bytes.len() && bytes[i] != b'/' {
+                i += 1;
+            }
+            count += 1;
+            end = i;
+        }
+        if count < n {
+            bytes.len()
+        } else {
+            end
+        }
+    }
+
+    /// Creates an owned `PathBuf` containing the first `n` components of `self`.
+    ///
+    /// For absolute paths, the root directory `/` is the first component.  If the path has
+    /// fewer than `n` components, all of them are kept.
+    ///
+    /// ```
+    ///# use littlefs2_core::path;
+    /// let path = path!("/very/deep/nested/file");
+    /// assert_eq!(path.take_components(3).as_str(), "/very/deep");
+    /// assert_eq!(path!("a/b/c").take_components(1).as_str(), "a");
+    /// assert_eq!(path!("a/b/c").take_components(5).as_str(), "a/b/c");
+    /// ```
+    pub fn take_components(&self, n: usize) -> PathBuf {
+        let mut path = PathBuf::from(self);
+        path.truncate_components(n);
+        path
+    }
+
+    /// Like [`Path::strip_prefix`][], but returns an owned `PathBuf`.
+    ///
+    /// ```
+    ///# use littlefs2_core::{path, path_buf};
+    /// let path = path!("/some/path");
+    /// assert_eq!(path.strip_prefix_owned(path!("/some")), Some(path_buf!("path")));
+    /// ```
+    pub fn strip_prefix_owned(&self, base: &Path) -> Option<PathBuf> {
+        self.strip_prefix(base).map(PathBuf::from)
+    }
 }
 
 impl AsRef<str> for Path {
@@ -376,6 +964,29 @@ impl fmt::Display for Path {
     }
 }
 
+/// Helper for displaying a path with escaped control characters
+///
+/// See documentation for [`Path::display_escaped`][]
+pub struct Escaped<'a> {
+    path: &'a str,
+}
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in self.path.split_inclusive(|c: char| c.is_ascii_control()) {
+            match part.as_bytes().split_last() {
+                Some((last, rest)) if last.is_ascii_control() => {
+                    // SAFETY: ASCII is valid UTF-8
+                    f.write_str(unsafe { str::from_utf8_unchecked(rest) })?;
+                    write!(f, "\\x{last:02x}")?;
+                }
+                _ => f.write_str(part)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 impl<'b> TryFrom<&'b [u8]> for &'b Path {
     type Error = PathError;
 
@@ -458,7 +1069,22 @@ impl Default for PathBuf {
 }
 
 impl PathBuf {
-    pub const MAX_SIZE: usize = 255;
+    /// The maximum length of a path in bytes, not including the trailing nul.
+    ///
+    /// This is 255 bytes by default.  To reduce the size of `PathBuf` in memory-constrained
+    /// builds, it can be reduced to 128 or 64 bytes with the `path-max-128` and `path-max-64`
+    /// features.  If several of them are enabled, the smallest value is used.  As cargo unifies
+    /// features, this affects all crates in the build that use `littlefs2-core`.
+    ///
+    /// With a reduced limit, names of existing files may be too long for a `PathBuf`.
+    /// Operations that return such a name fail with `FILENAME_TOO_LONG`.
+    pub const MAX_SIZE: usize = if cfg!(feature = "path-max-64") {
+        64
+    } else if cfg!(feature = "path-max-128") {
+        128
+    } else {
+        255
+    };
     pub const MAX_SIZE_PLUS_ONE: usize = Self::MAX_SIZE + 1;
 
     pub const fn new() -> Self {
@@ -511,6 +1137,34 @@ impl PathBuf {
         self.as_path().as_str()
     }
 
+    /// Creates a path from a byte slice, with or without trailing nul.
+    ///
+    /// This is equivalent to the `TryFrom<&[u8]>` implementation and never panics, so it can be
+    /// used with data read from storage or received over the network.  It returns
+    /// [`PathError::NotCStr`][] if the slice contains an inner nul byte,
+    /// [`PathError::NotAscii`][] if it contains non-ASCII bytes and [`PathError::TooLarge`][] if
+    /// it is longer than [`PathBuf::MAX_SIZE`][] bytes, not including the trailing nul.
+    ///
+    /// ```
+    ///# use littlefs2_core::{PathBuf, PathError};
+    /// assert_eq!(PathBuf::try_from_bytes(b"/some/path").unwrap().as_str(), "/some/path");
+    /// assert_eq!(PathBuf::try_from_bytes(b"some\0path"), Err(PathError::NotCStr));
+    /// ```
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::try_from(bytes)
+    }
+
+    /// Keeps the first `keep` components of the path and removes the rest.
+    ///
+    /// See [`Path::take_components`][].
+    pub fn truncate_components(&mut self, keep: usize) {
+        let len = self.components_len(keep);
+        if len + 1 < self.len {
+            self.buf[len..].fill(0);
+            self.len = len + 1;
+        }
+    }
+
     pub fn clear(&mut self) {
         self.buf = [0; Self::MAX_SIZE_PLUS_ONE];
         self.len = 1;
@@ -526,7 +1180,44 @@ impl PathBuf {
         PathBuf { buf, len }
     }
 
+    /// Extends `self` with `path`, or returns an error if the result would be too long.
+    ///
+    /// In contrast to [`PathBuf::push`][], this does not panic and can be used with untrusted
+    /// input.  On error, `self` is not modified.
+    ///
+    /// ```
+    ///# use littlefs2_core::{path, PathBuf, PathError};
+    /// let mut path = PathBuf::from(path!("/some"));
+    /// path.try_push(path!("path")).unwrap();
+    /// assert_eq!(path.as_str(), "/some/path");
+    ///
+    /// let long = PathBuf::try_from([b'a'; PathBuf::MAX_SIZE].as_slice()).unwrap();
+    /// assert_eq!(path.try_push(&long), Err(PathError::TooLarge));
+    /// assert_eq!(path.as_str(), "/some/path");
+    /// ```
+    pub fn try_push(&mut self, path: &Path) -> Result<()> {
+        let src = path.as_str();
+        if !matches!(src, "" | "/") {
+            let needs_separator = self
+                .as_str()
+                .as_bytes()
+                .last()
+                .map(|byte| *byte != b'/')
+                .unwrap_or(false);
+            if self.len + src.len() + usize::from(needs_separator) > Self::MAX_SIZE_PLUS_ONE {
+                return Err(PathError::TooLarge);
+            }
+        }
+        self.push(path);
+        Ok(())
+    }
+
     /// Extends `self` with `path`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting path would be longer than [`PathBuf::MAX_SIZE`][].  Use
+    /// [`PathBuf::try_push`][] for untrusted input.
     pub fn push(&mut self, path: &Path) {
         match path.as_ref() {
             // no-operation
@@ -579,6 +1270,42 @@ impl PathBuf {
     }
 }
 
+/// Joins two paths with the `/` operator, see [`Path::join`][].
+///
+/// This makes it possible to build paths like `root / dir / file`.  Like [`Path::join`][], it
+/// panics if the resulting path would be too long, so use [`Path::try_join`][] for untrusted
+/// input.
+///
+/// ```
+///# use littlefs2_core::path;
+/// let path = path!("/root") / path!("dir") / path!("file.txt");
+/// assert_eq!(path.as_str(), "/root/dir/file.txt");
+/// ```
+impl ops::Div<&Path> for &Path {
+    type Output = PathBuf;
+
+    fn div(self, rhs: &Path) -> PathBuf {
+        self.join(rhs)
+    }
+}
+
+impl ops::Div<&Path> for PathBuf {
+    type Output = PathBuf;
+
+    fn div(mut self, rhs: &Path) -> PathBuf {
+        self.push(rhs);
+        self
+    }
+}
+
+impl ops::Div<&Path> for &PathBuf {
+    type Output = PathBuf;
+
+    fn div(self, rhs: &Path) -> PathBuf {
+        self.join(rhs)
+    }
+}
+
 impl From<&Path> for PathBuf {
     #[inline(never)]
     fn from(path: &Path) -> Self {
@@ -709,22 +1436,98 @@ impl core::cmp::PartialEq for PathBuf {
 
 impl core::cmp::Eq for PathBuf {}
 
-// use core::cmp::Ordering;
+impl PartialEq<PathBuf> for Path {
+    fn eq(&self, other: &PathBuf) -> bool {
+        self == other.as_path()
+    }
+}
 
-// impl Ord for PathBuf {
-//     fn cmp(&self, other: &Self) -> Ordering {
-//         self.len.cmp(&other.len)
-//     }
-// }
+impl PartialEq<PathBuf> for &Path {
+    fn eq(&self, other: &PathBuf) -> bool {
+        *self == other.as_path()
+    }
+}
 
-// impl PartialOrd for PathBuf {
-//     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-//         Some(self.cmp(other))
-//     }
-// }
+impl PartialEq<Path> for PathBuf {
+    fn eq(&self, other: &Path) -> bool {
+        self.as_path() == other
+    }
+}
+
+impl PartialEq<&Path> for PathBuf {
+    fn eq(&self, other: &&Path) -> bool {
+        self.as_path() == *other
+    }
+}
+
+/// Orders paths like littlefs orders the entries of a directory, see [`Path::cmp_lfs`][].
+///
+/// This ordering differs from the lexical ordering of the strings if one path is a prefix of
+/// the other:  littlefs sorts the longer path first.  Use [`StrOrderedPath`][] for the lexical
+/// ordering.
+impl Ord for PathBuf {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_lfs(other)
+    }
+}
+
+impl PartialOrd for PathBuf {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A `PathBuf` that is ordered by its string representation, see [`Path::cmp_str`][].
+///
+/// `PathBuf` implements `Ord` using [`Path::cmp_lfs`][], which matches the order in which
+/// littlefs returns the entries of a directory.  Because littlefs sorts a name before all names
+/// that it is a prefix of, this is not the lexical order.  Use this wrapper as the key of a
+/// `BTreeMap` or similar collections if the lexical order is required instead.
+///
+/// ```
+///# use littlefs2_core::{path, PathBuf, StrOrderedPath};
+/// let a = PathBuf::from(path!("file"));
+/// let b = PathBuf::from(path!("file.txt"));
+/// assert!(a > b);
+/// assert!(StrOrderedPath::from(a) < StrOrderedPath::from(b));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StrOrderedPath(pub PathBuf);
+
+impl StrOrderedPath {
+    pub fn into_inner(self) -> PathBuf {
+        self.0
+    }
+}
+
+impl From<PathBuf> for StrOrderedPath {
+    fn from(path: PathBuf) -> Self {
+        Self(path)
+    }
+}
+
+impl ops::Deref for StrOrderedPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Ord for StrOrderedPath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp_str(&other.0)
+    }
+}
+
+impl PartialOrd for StrOrderedPath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
 /// Errors that arise from converting byte buffers into paths
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PathError {
     /// Byte buffer contains non-ASCII characters
     NotAscii,
@@ -732,13 +1535,15 @@ pub enum PathError {
     NotCStr,
     /// Byte buffer is too long (longer than [`PathBuf::MAX_SIZE_PLUS_ONE`][])
     TooLarge,
+    /// Path is absolute or escapes its base directory, see [`Path::join_rooted`][]
+    NotContained,
 }
 
 type Result<T> = core::result::Result<T, PathError>;
 
 #[cfg(test)]
 mod tests {
-    use super::{Path, PathBuf};
+    use super::{Path, PathBuf, PathError};
     use crate::path;
 
     const EMPTY: &Path = path!("");
@@ -797,6 +1602,60 @@ mod tests {
         assert!(Path::from_bytes_with_nul(b"abc\0def").is_err());
     }
 
+    #[test]
+    fn ordering() {
+        extern crate std;
+        use super::StrOrderedPath;
+        use std::collections::BTreeSet;
+
+        let paths = ["b", "a", "ab", "a/b", "a.b"].map(|p| PathBuf::try_from(p).unwrap());
+        let lfs: BTreeSet<_> = paths.iter().cloned().collect();
+        let lfs: std::vec::Vec<_> = lfs.iter().map(|p| p.as_str()).collect();
+        assert_eq!(lfs, ["a.b", "a/b", "ab", "a", "b"]);
+
+        let str: BTreeSet<_> = paths.iter().cloned().map(StrOrderedPath::from).collect();
+        let str: std::vec::Vec<_> = str.iter().map(|p| p.as_str()).collect();
+        assert_eq!(str, ["a", "a.b", "a/b", "ab", "b"]);
+    }
+
+    #[test]
+    fn display_escaped() {
+        extern crate std;
+        use std::string::ToString;
+
+        assert_eq!(EMPTY.display_escaped().to_string(), "");
+        assert_eq!(path!("/a b/c~").display_escaped().to_string(), "/a b/c~");
+        assert_eq!(path!("\t").display_escaped().to_string(), "\\x09");
+        assert_eq!(
+            path!("a\x01\x7f\nb").display_escaped().to_string(),
+            "a\\x01\\x7f\\x0ab"
+        );
+        assert_eq!(path!("a\tb").to_string(), "a\tb");
+    }
+
+    #[test]
+    fn try_from_bytes() {
+        assert_eq!(PathBuf::try_from_bytes(b"").unwrap().as_str(), "");
+        assert_eq!(PathBuf::try_from_bytes(b"a/b\0").unwrap().as_str(), "a/b");
+
+        assert_eq!(PathBuf::try_from_bytes(b"a\0b"), Err(PathError::NotCStr));
+        assert_eq!(PathBuf::try_from_bytes(b"a\0\0"), Err(PathError::NotCStr));
+        assert_eq!(PathBuf::try_from_bytes(b"\0\0"), Err(PathError::NotCStr));
+
+        assert_eq!(
+            PathBuf::try_from_bytes(b"caf\xc3\xa9"),
+            Err(PathError::NotAscii)
+        );
+        assert_eq!(PathBuf::try_from_bytes(&[0xff]), Err(PathError::NotAscii));
+
+        let max = [b'a'; PathBuf::MAX_SIZE + 1];
+        assert!(PathBuf::try_from_bytes(&max[..PathBuf::MAX_SIZE]).is_ok());
+        assert_eq!(PathBuf::try_from_bytes(&max), Err(PathError::TooLarge));
+        let mut max_with_nul = [b'a'; PathBuf::MAX_SIZE_PLUS_ONE];
+        max_with_nul[PathBuf::MAX_SIZE] = 0;
+        assert!(PathBuf::try_from_bytes(&max_with_nul).is_ok());
+    }
+
     #[test]
     fn trailing_nuls() {
         assert_eq!(
@@ -886,6 +1745,21 @@ mod tests {
         assert!(ancestors.next().is_none());
     }
 
+    #[test]
+    fn iter_with_last() {
+        let mut iter = path!("a/b/c/").iter_with_last();
+        assert_eq!(iter.next(), Some((path!("a").into(), false)));
+        assert_eq!(iter.next(), Some((path!("b").into(), false)));
+        assert_eq!(iter.next(), Some((path!("c").into(), true)));
+        assert!(iter.next().is_none());
+
+        let mut iter = path!("file").iter_with_last();
+        assert_eq!(iter.next(), Some((path!("file").into(), true)));
+        assert!(iter.next().is_none());
+
+        assert!(path!("").iter_with_last().next().is_none());
+    }
+
     #[test]
     fn file_name() {
         let path = path!("/some/path/.././file.extension");
@@ -900,4 +1774,241 @@ mod tests {
         let path = path!("/some/path/.././file.extension/");
         assert_eq!(path.file_name(), None);
     }
+
+    #[test]
+    fn div() {
+        let root = path!("/root");
+        let dir = PathBuf::from(path!("dir"));
+        assert_eq!((root / &dir / path!("file")).as_str(), "/root/dir/file");
+        assert_eq!((&dir / path!("file")).as_str(), "dir/file");
+        assert_eq!((dir / SLASH).as_str(), "/");
+        assert_eq!((EMPTY / root).as_str(), "/root");
+    }
+
+    #[test]
+    fn try_join() {
+        let long = PathBuf::try_from([b'a'; PathBuf::MAX_SIZE - 1].as_slice()).unwrap();
+        assert_eq!(path!("b").try_join(&long), Err(PathError::TooLarge));
+        assert_eq!(path!("b/").try_join(&long), Err(PathError::TooLarge));
+        let joined = SLASH.try_join(&long).unwrap();
+        assert_eq!(joined.as_str().len(), PathBuf::MAX_SIZE);
+        assert_eq!(joined.try_join(SLASH).unwrap().as_str(), "/");
+        assert_eq!(joined.try_join(EMPTY).unwrap(), joined);
+        assert_eq!(joined.try_join(path!("b")), Err(PathError::TooLarge));
+    }
+
+    #[test]
+    fn clone_iterators() {
+        let path = path!("/a/b");
+        let mut ancestors = path.ancestors();
+        ancestors.next();
+        let mut fork = ancestors.clone();
+        assert_eq!(fork.next().as_deref(), Some(path!("/a")));
+        assert_eq!(fork.next().as_deref(), Some(SLASH));
+        assert_eq!(ancestors.next().as_deref(), Some(path!("/a")));
+
+        let mut iter = path.iter();
+        iter.next();
+        let mut fork = iter.clone();
+        assert_eq!(fork.next().as_deref(), Some(path!("a")));
+        assert_eq!(fork.next().as_deref(), Some(path!("b")));
+        assert!(fork.next().is_none());
+        assert_eq!(iter.next().as_deref(), Some(path!("a")));
+    }
+
+    #[test]
+    fn is_contained() {
+        assert!(EMPTY.is_contained());
+        assert!(SLASH.is_contained());
+        assert!(path!("a").is_contained());
+        assert!(path!("a/../b").is_contained());
+        assert!(path!("a/b/../../c").is_contained());
+        assert!(path!("a/..").is_contained());
+        assert!(path!("./a/./..").is_contained());
+        assert!(path!("a//..").is_contained());
+        assert!(path!("..a/b..").is_contained());
+        assert!(path!("/a/../b").is_contained());
+
+        assert!(!path!("..").is_contained());
+        assert!(!path!("../etc").is_contained());
+        assert!(!path!("a/../../b").is_contained());
+        assert!(!path!("a/b/../../..").is_contained());
+        assert!(!path!("./..").is_contained());
+        assert!(!path!("/..").is_contained());
+        assert!(!path!("/../etc").is_contained());
+    }
+
+    #[test]
+    fn take_components() {
+        let take = |path: &Path, n| path.take_components(n);
+        assert_eq!(take(EMPTY, 0).as_str(), "");
+        assert_eq!(take(EMPTY, 1).as_str(), "");
+        assert_eq!(take(SLASH, 0).as_str(), "");
+        assert_eq!(take(SLASH, 1).as_str(), "/");
+        assert_eq!(take(path!("/a/b"), 1).as_str(), "/");
+        assert_eq!(take(path!("/a/b"), 2).as_str(), "/a");
+        assert_eq!(take(path!("/a/b"), 3).as_str(), "/a/b");
+        assert_eq!(take(path!("a/b/"), 1).as_str(), "a");
+        assert_eq!(take(path!("a/b/"), 2).as_str(), "a/b");
+        assert_eq!(take(path!("a/b/"), 3).as_str(), "a/b/");
+        assert_eq!(take(path!("a//b//c"), 2).as_str(), "a//b");
+        assert_eq!(take(path!("abc/def"), 0).as_str(), "");
+
+        let mut path = PathBuf::from(path!("/a/b/c"));
+        path.truncate_components(2);
+        assert_eq!(path, PathBuf::from(path!("/a")));
+        path.push(path!("d"));
+        assert_eq!(path.as_str(), "/a/d");
+    }
+
+    #[test]
+    fn split_first() {
+        fn split<'a>(first: &str, rest: &'a Path) -> Option<(PathBuf, &'a Path)> {
+            Some((PathBuf::try_from(first).unwrap(), rest))
+        }
+
+        assert_eq!(EMPTY.split_first(), None);
+        assert_eq!(SLASH.split_first(), split("/", EMPTY));
+        assert_eq!(path!("a").split_first(), split("a", EMPTY));
+        assert_eq!(path!("a/").split_first(), split("a", EMPTY));
+        assert_eq!(path!("a/b/c").split_first(), split("a", path!("b/c")));
+        assert_eq!(path!("/a/b").split_first(), split("/", path!("a/b")));
+        assert_eq!(path!("a//b").split_first(), split("a", path!("b")));
+    }
+
+    #[test]
+    fn eq_path_buf() {
+        let path = path!("/a/b");
+        let buf = PathBuf::from(path);
+        assert!(path == buf);
+        assert!(*path == buf);
+        assert!(buf == path);
+        assert!(buf == *path);
+        assert!(buf != path!("/a"));
+        assert!(path!("/a") != buf);
+        assert_eq!(buf, path);
+        assert_eq!(path, buf);
+    }
+
+    #[test]
+    fn eq_ignoring_trailing_slash() {
+        assert!(path!("a").eq_ignoring_trailing_slash(path!("a/")));
+        assert!(path!("a/").eq_ignoring_trailing_slash(path!("a")));
+        assert!(path!("/").eq_ignoring_trailing_slash(path!("/")));
+        assert!(!path!("a").eq_ignoring_trailing_slash(path!("b/")));
+        assert!(!path!("/a").eq_ignoring_trailing_slash(path!("a/")));
+        assert_ne!(path!("a"), path!("a/"));
+    }
+
+    #[test]
+    fn dotted_extension() {
+        assert_eq!(path!("a.b").dotted_extension(), Some(path!(".b")));
+        assert_eq!(path!("/a.b/c.d.e").dotted_extension(), Some(path!(".e")));
+        assert_eq!(path!("/a.b/c").dotted_extension(), None);
+        assert_eq!(path!("..").dotted_extension(), None);
+        assert_eq!(path!("").dotted_extension(), None);
+    }
+
+    #[test]
+    fn split_scheme() {
+        assert_eq!(
+            path!("flash:/a:b").split_scheme(),
+            Some(("flash", path!("/a:b")))
+        );
+        assert_eq!(path!("flash").split_scheme(), None);
+        assert_eq!(path!("fl-ash:/a").split_scheme(), None);
+        assert_eq!(path!("").split_scheme(), None);
+    }
+
+    #[test]
+    fn join_rooted() {
+        let root = path!("/sandbox");
+        assert_eq!(
+            root.join_rooted(path!("a/b")).unwrap(),
+            path!("/sandbox/a/b")
+        );
+        assert_eq!(root.join_rooted(path!("")).unwrap(), path!("/sandbox"));
+        assert_eq!(
+            root.join_rooted(path!("a/..")).unwrap(),
+            path!("/sandbox/a/..")
+        );
+        for rel in [
+            path!("/"),
+            path!("/a"),
+            path!("//a"),
+            path!(".."),
+            path!("../sandbox2"),
+            path!("a/../.."),
+            path!("./../a"),
+        ] {
+            assert_eq!(root.join_rooted(rel), Err(PathError::NotContained), "{rel}");
+        }
+
+        let long = PathBuf::try_from([b'a'; PathBuf::MAX_SIZE - 4].as_slice()).unwrap();
+        assert_eq!(root.join_rooted(&long), Err(PathError::TooLarge));
+    }
+
+    #[test]
+    fn ends_with_any_extension() {
+        assert!(path!("a.b").ends_with_any_extension(&["c", "b"]));
+        assert!(!path!("a.b").ends_with_any_extension(&[]));
+        assert!(!path!("a.b.c").ends_with_any_extension(&["b.c"]));
+        assert!(!path!("").ends_with_any_extension(&[""]));
+        assert!(path!("a.").ends_with_any_extension(&[""]));
+        assert!(path!("a.B").ends_with_any_extension_ignore_case(&["b"]));
+        assert!(!path!(".b").ends_with_any_extension_ignore_case(&["b"]));
+    }
+
+    #[test]
+    fn has_extension() {
+        assert!(path!("a.b").has_extension("b"));
+        assert!(path!("a.").has_extension(""));
+        assert!(path!("/a/b.c.d").has_extension("d"));
+        assert!(!path!("/a.b/c").has_extension("b"));
+        assert!(!path!("..").has_extension(""));
+        assert!(!path!(".").has_extension(""));
+        assert!(!EMPTY.has_extension(""));
+        assert!(!SLASH.has_extension(""));
+    }
+
+    #[test]
+    fn with_file_name() {
+        let name = path!("c");
+        assert_eq!(&*path!("/a/b").with_file_name(name), path!("/a/c"));
+        assert_eq!(&*path!("/a/b.txt").with_file_name(name), path!("/a/c"));
+        assert_eq!(&*path!("/a").with_file_name(name), path!("/c"));
+        assert_eq!(&*path!("a/b").with_file_name(name), path!("a/c"));
+        assert_eq!(&*path!("a").with_file_name(name), path!("c"));
+        assert_eq!(&*path!("a/").with_file_name(name), path!("a/c"));
+        assert_eq!(&*SLASH.with_file_name(name), path!("/c"));
+        assert_eq!(&*EMPTY.with_file_name(name), path!("c"));
+        assert_eq!(&*path!("/a/b").with_file_name(EMPTY), path!("/a"));
+    }
+
+    #[test]
+    fn strip_prefix() {
+        let path = path!("/a/b");
+        assert_eq!(path.strip_prefix(SLASH), Some(path!("a/b")));
+        assert_eq!(path.strip_prefix(path), Some(EMPTY));
+        assert_eq!(path.strip_prefix(path!("/a")), Some(path!("b")));
+        assert_eq!(path.strip_prefix(path!("/a/")), Some(path!("b")));
+        assert_eq!(path.strip_prefix(EMPTY), Some(path));
+        assert_eq!(path.strip_prefix(path!("/a/b/c")), None);
+        assert_eq!(path.strip_prefix(path!("a")), None);
+        assert_eq!(path!("/ab").strip_prefix(path!("/a")), None);
+
+        assert_eq!(SLASH.strip_prefix(SLASH), Some(EMPTY));
+        assert_eq!(EMPTY.strip_prefix(EMPTY), Some(EMPTY));
+        assert_eq!(EMPTY.strip_prefix(SLASH), None);
+
+        let path = path!("a/b/");
+        assert_eq!(path.strip_prefix(path!("a")), Some(path!("b/")));
+        assert_eq!(path.strip_prefix(path!("a/b")), Some(EMPTY));
+
+        assert_eq!(
+            path!("/a/b").strip_prefix_owned(path!("/a")),
+            Some(PathBuf::from(path!("b")))
+        );
+        assert_eq!(path!("/a/b").strip_prefix_owned(path!("/b")), None);
+    }
 }
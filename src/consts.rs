@@ -5,7 +5,30 @@ pub use generic_array::typenum::consts::*;
 
 pub const PATH_MAX: usize = littlefs2_core::PathBuf::MAX_SIZE;
 pub const PATH_MAX_PLUS_ONE: usize = littlefs2_core::PathBuf::MAX_SIZE_PLUS_ONE;
+// The path buffers rely on this to reserve space for the trailing nul.
+const _: () = assert!(PATH_MAX_PLUS_ONE == PATH_MAX + 1);
+/// The maximum length of a single path component plus one, as configured as `name_max` for
+/// littlefs.
+///
+/// This limit applies to each file or directory name, while [`PATH_MAX`][] applies to the whole
+/// path.  As `PATH_MAX` is at most 255 bytes, every component of a valid path is short enough,
+/// and littlefs never returns [`Error::FILENAME_TOO_LONG`](crate::io::Error::FILENAME_TOO_LONG)
+/// for paths passed to this crate.
 pub const FILENAME_MAX_PLUS_ONE: u32 = 255 + 1;
+// Components can't exceed the name limit as long as paths are not longer than it.
+const _: () = assert!(PATH_MAX < FILENAME_MAX_PLUS_ONE as usize);
 pub const FILEBYTES_MAX: u32 = crate::ll::LFS_FILE_MAX as _;
 pub const ATTRBYTES_MAX: u32 = littlefs2_core::Attribute::MAX_SIZE;
+/// The id of the attribute that marks a file as a link, see
+/// [`Filesystem::create_link`](crate::fs::Filesystem::create_link).
+pub const LINK_ATTRIBUTE_ID: u8 = 0xff;
+/// The value of the [`LINK_ATTRIBUTE_ID`][] attribute of a link.
+pub const LINK_MARKER: &[u8] = b"link";
+/// The id of the attribute that stores the modification timestamp of an entry, see
+/// [`Filesystem::read_dir_since`](crate::fs::Filesystem::read_dir_since).
+///
+/// The timestamp is stored as a `u64` in little-endian byte order.  Its unit and epoch are up to
+/// the application.  littlefs does not update it, it must be set with
+/// [`Filesystem::set_attribute`](crate::fs::Filesystem::set_attribute) when an entry is changed.
+pub const TIMESTAMP_ATTRIBUTE_ID: u8 = 0xfe;
 pub const LOOKAHEADWORDS_SIZE: u32 = 16;
@@ -1,7 +1,9 @@
 //! The `Storage`, `Read`, `Write` and `Seek` driver.
 #![allow(non_camel_case_types)]
 
-use generic_array::ArrayLength;
+use core::fmt;
+
+use generic_array::{typenum::Unsigned, ArrayLength};
 
 use crate::io::Result;
 
@@ -34,6 +36,18 @@ pub trait Storage {
     /// [At least 128](https://github.com/littlefs-project/littlefs/issues/264#issuecomment-519963153). Stored in superblock.
     const BLOCK_SIZE: usize;
 
+    /// Size of the physical erase unit in bytes.  Defaults to `BLOCK_SIZE`.
+    ///
+    /// `BLOCK_SIZE` is the logical block size used by littlefs and must be a multiple of
+    /// `ERASE_SIZE`.  If it is larger, littlefs erases one logical block by calling
+    /// [`erase`](Storage::erase) once for each of the `BLOCK_SIZE / ERASE_SIZE` erase units.
+    ///
+    /// Larger logical blocks reduce the number of blocks littlefs has to track and the metadata
+    /// overhead, but every metadata compaction and every file block allocation then erases and
+    /// rewrites more flash, so small writes cause more wear and take longer.  Files smaller than
+    /// a block still occupy a full block.
+    const ERASE_SIZE: usize = Self::BLOCK_SIZE;
+
     /// Number of erasable blocks.
     /// Hence storage capacity is `BLOCK_COUNT * BLOCK_SIZE`
     const BLOCK_COUNT: usize;
@@ -83,13 +97,106 @@ pub trait Storage {
 
     /// Read data from the storage device.
     /// Guaranteed to be called only with bufs of length a multiple of READ_SIZE.
+    ///
+    /// `off` is an absolute byte offset from the start of the storage, not a block index.
+    /// littlefs' block and in-block offset are combined as `block * BLOCK_SIZE + offset`, and a
+    /// read never crosses a block boundary.
     fn read(&mut self, off: usize, buf: &mut [u8]) -> Result<usize>;
     /// Write data to the storage device.
     /// Guaranteed to be called only with bufs of length a multiple of WRITE_SIZE.
+    ///
+    /// `off` is an absolute byte offset like for [`read`](Storage::read).
     fn write(&mut self, off: usize, data: &[u8]) -> Result<usize>;
     /// Erase data from the storage device.
-    /// Guaranteed to be called only with bufs of length a multiple of BLOCK_SIZE.
+    /// Guaranteed to be called only with bufs of length a multiple of ERASE_SIZE.
+    ///
+    /// `off` is the absolute byte offset of the first erase unit to erase, so it is a multiple
+    /// of `ERASE_SIZE`, and `len` is the number of bytes to erase.  Note that the offset comes
+    /// first and that both are given in bytes, not in blocks.
     fn erase(&mut self, off: usize, len: usize) -> Result<usize>;
+
+    /// Check that the geometry constants satisfy the requirements of littlefs.
+    ///
+    /// The default implementation checks the constraints documented for the associated
+    /// constants and types.  It is called when formatting and mounting the filesystem, which
+    /// fail with [`Error::INVALID`](crate::io::Error::INVALID) if the geometry is invalid.  Call
+    /// it directly to find out which constraint is violated.
+    fn validate_geometry(&self) -> core::result::Result<(), GeometryError> {
+        let read_size = Self::READ_SIZE;
+        let write_size = Self::WRITE_SIZE;
+        let block_size = Self::BLOCK_SIZE;
+        let cache_size = Self::CACHE_SIZE::USIZE;
+
+        if read_size == 0 || write_size == 0 || cache_size == 0 {
+            return Err(GeometryError::ZeroSize);
+        }
+        if Self::BLOCK_COUNT == 0 {
+            return Err(GeometryError::ZeroBlockCount);
+        }
+        if Self::LOOKAHEAD_SIZE::USIZE == 0 {
+            return Err(GeometryError::ZeroLookaheadSize);
+        }
+        if Self::BLOCK_CYCLES == 0 || Self::BLOCK_CYCLES < -1 {
+            return Err(GeometryError::InvalidBlockCycles);
+        }
+        if block_size < 128 {
+            return Err(GeometryError::BlockSizeTooSmall);
+        }
+        if cache_size % read_size != 0 || cache_size % write_size != 0 {
+            return Err(GeometryError::CacheSizeNotMultipleOfReadWriteSize);
+        }
+        if block_size % cache_size != 0 {
+            return Err(GeometryError::BlockSizeNotMultipleOfCacheSize);
+        }
+        if Self::ERASE_SIZE == 0 || block_size % Self::ERASE_SIZE != 0 {
+            return Err(GeometryError::BlockSizeNotMultipleOfEraseSize);
+        }
+        Ok(())
+    }
     // /// Synchronize writes to the storage device.
     // fn sync(&mut self) -> Result<usize>;
 }
+
+/// A violated geometry constraint, see [`Storage::validate_geometry`][].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum GeometryError {
+    /// `READ_SIZE`, `WRITE_SIZE` or `CACHE_SIZE` is zero.
+    ZeroSize,
+    /// `BLOCK_COUNT` is zero.
+    ZeroBlockCount,
+    /// `LOOKAHEAD_SIZE` is zero.
+    ZeroLookaheadSize,
+    /// `BLOCK_CYCLES` is neither positive nor -1.
+    InvalidBlockCycles,
+    /// `BLOCK_SIZE` is smaller than 128 bytes.
+    BlockSizeTooSmall,
+    /// `CACHE_SIZE` is not a multiple of both `READ_SIZE` and `WRITE_SIZE`.
+    CacheSizeNotMultipleOfReadWriteSize,
+    /// `BLOCK_SIZE` is not a multiple of `CACHE_SIZE`.
+    BlockSizeNotMultipleOfCacheSize,
+    /// `ERASE_SIZE` is zero or `BLOCK_SIZE` is not a multiple of it.
+    BlockSizeNotMultipleOfEraseSize,
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::ZeroSize => "read, write and cache size must not be zero",
+            Self::ZeroBlockCount => "block count must not be zero",
+            Self::ZeroLookaheadSize => "lookahead size must not be zero",
+            Self::InvalidBlockCycles => "block cycles must be positive or -1",
+            Self::BlockSizeTooSmall => "block size must be at least 128 bytes",
+            Self::CacheSizeNotMultipleOfReadWriteSize => {
+                "cache size must be a multiple of the read and write size"
+            }
+            Self::BlockSizeNotMultipleOfCacheSize => {
+                "block size must be a multiple of the cache size"
+            }
+            Self::BlockSizeNotMultipleOfEraseSize => {
+                "block size must be a non-zero multiple of the erase size"
+            }
+        };
+        f.write_str(message)
+    }
+}
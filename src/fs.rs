@@ -4,7 +4,10 @@ use core::ffi::{c_int, c_void};
 use core::ptr::addr_of;
 use core::ptr::addr_of_mut;
 use core::{
-    cell::{RefCell, UnsafeCell},
+    cell::{Cell, RefCell, UnsafeCell},
+    cmp, fmt,
+    iter::FusedIterator,
+    marker::PhantomData,
     mem, slice,
 };
 use generic_array::typenum::marker_traits::Unsigned;
@@ -16,10 +19,11 @@ pub type Bytes<SIZE> = generic_array::GenericArray<u8, SIZE>;
 pub use littlefs2_core::{Attribute, DirEntry, FileOpenFlags, FileType, Metadata};
 
 use crate::{
+    consts::{LINK_ATTRIBUTE_ID, LINK_MARKER, TIMESTAMP_ATTRIBUTE_ID},
     driver,
     io::{self, Error, OpenSeekFrom, Result},
-    path::{Path, PathBuf},
-    DISK_VERSION,
+    path::{self, Path, PathBuf},
+    Version, DISK_VERSION,
 };
 
 fn error_code_from<T>(result: Result<T>) -> ll::lfs_error {
@@ -36,6 +40,13 @@ fn result_from<T>(return_value: T, error_code: ll::lfs_error) -> Result<T> {
     }
 }
 
+fn validate_geometry<S: driver::Storage>(storage: &S) -> Result<()> {
+    storage.validate_geometry().map_err(|_error| {
+        error_now!("invalid storage geometry: {}", _error);
+        Error::INVALID
+    })
+}
+
 pub fn u32_result(return_value: i32) -> Result<u32> {
     u32::try_from(return_value).map_err(|_| {
         let error_code = c_int::try_from(return_value).unwrap_or(c_int::MIN);
@@ -120,9 +131,7 @@ impl<Storage: driver::Storage> Allocation<Storage> {
         debug_assert!(filename_max_plus_one <= 1_022 + 1);
         // limitation of ll-bindings
         debug_assert!(filename_max_plus_one == 255 + 1);
-        let path_max_plus_one: u32 = crate::consts::PATH_MAX_PLUS_ONE as _;
-        // TODO: any upper limit?
-        debug_assert!(path_max_plus_one >= filename_max_plus_one);
+        // PATH_MAX can be reduced below the name limit with a feature, see PathBuf::MAX_SIZE
         let file_max = crate::consts::FILEBYTES_MAX;
         assert!(file_max > 0);
         assert!(file_max <= 2_147_483_647);
@@ -173,6 +182,366 @@ impl<Storage: driver::Storage> Allocation<Storage> {
     }
 }
 
+/// Index of the block of a CTZ skip-list that contains the byte at `off`, see `lfs_ctz_index`.
+fn ctz_index(block_size: usize, off: usize) -> usize {
+    let b = block_size - 2 * 4;
+    let i = off / b;
+    if i == 0 {
+        return 0;
+    }
+    (off - 4 * ((i - 1).count_ones() as usize + 2)) / b
+}
+
+/// CRC-32 with the reflected polynomial `0x04c11db7` and without a final XOR, see `lfs_crc`.
+fn lfs_crc(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    crc
+}
+
+const LFS_BLOCK_NULL: ll::lfs_block_t = ll::lfs_block_t::MAX;
+const LFS_TYPE_TAIL: u32 = 0x600;
+const LFS_TYPE_CCRC: u32 = 0x500;
+
+/// Size of a metadata tag including its data, see `lfs_tag_dsize`.
+fn tag_dsize(tag: u32) -> usize {
+    let is_delete = ((tag << 22) as i32 >> 22) == -1;
+    4 + (tag.wrapping_add(is_delete.into()) & 0x3ff) as usize
+}
+
+/// Reads metadata blocks through a buffer of one cache line, independently of the caches of
+/// littlefs.
+struct MetadataReader<S: driver::Storage> {
+    config: *const ll::lfs_config,
+    cache: Bytes<S::CACHE_SIZE>,
+    cached: Option<(ll::lfs_block_t, usize)>,
+}
+
+impl<S: driver::Storage> MetadataReader<S> {
+    fn new(config: *const ll::lfs_config) -> Self {
+        Self {
+            config,
+            cache: Default::default(),
+            cached: None,
+        }
+    }
+
+    fn read(&mut self, block: ll::lfs_block_t, off: usize, buf: &mut [u8]) -> Result<()> {
+        let line_size = self.cache.len();
+        for (off, byte) in (off..).zip(buf) {
+            let line = off - off % line_size;
+            if self.cached != Some((block, line)) {
+                self.cached = None;
+                let return_code = Filesystem::<S>::lfs_config_read(
+                    self.config,
+                    block,
+                    line as _,
+                    self.cache.as_mut_ptr().cast(),
+                    line_size as _,
+                );
+                result_from((), return_code)?;
+                self.cached = Some((block, line));
+            }
+            *byte = self.cache[off - line];
+        }
+        Ok(())
+    }
+
+    fn read_u32_le(&mut self, block: ll::lfs_block_t, off: usize) -> Result<u32> {
+        let mut word = [0; 4];
+        self.read(block, off, &mut word)?;
+        Ok(u32::from_le_bytes(word))
+    }
+
+    fn crc(&mut self, block: ll::lfs_block_t, off: usize, len: usize, mut crc: u32) -> Result<u32> {
+        let mut byte = [0];
+        for off in off..off + len {
+            self.read(block, off, &mut byte)?;
+            crc = lfs_crc(crc, &byte);
+        }
+        Ok(crc)
+    }
+
+    /// Check the CRCs of the commits in a metadata block like `lfs_dir_fetchmatch`, and return
+    /// the tail of the last valid commit, or `None` if the block has no valid commit.
+    ///
+    /// Like littlefs, the first tag that is not valid, does not fit into the block or fails the
+    /// CRC of its commit ends the block, as this is what an interrupted commit looks like.
+    fn last_commit(&mut self, block: ll::lfs_block_t) -> Result<Option<[ll::lfs_block_t; 2]>> {
+        let block_size = S::BLOCK_SIZE;
+        let mut word = [0; 4];
+        self.read(block, 0, &mut word)?;
+        let mut crc = lfs_crc(0xffff_ffff, &word);
+        let mut off = 0;
+        let mut ptag = 0xffff_ffff_u32;
+        let mut tail = [LFS_BLOCK_NULL; 2];
+        let mut committed_tail = None;
+
+        loop {
+            off += tag_dsize(ptag);
+            if off + 4 > block_size {
+                break;
+            }
+            self.read(block, off, &mut word)?;
+            crc = lfs_crc(crc, &word);
+            let tag = u32::from_be_bytes(word) ^ ptag;
+            if tag & 0x8000_0000 != 0 || off + tag_dsize(tag) > block_size {
+                break;
+            }
+            ptag = tag;
+
+            if (tag & 0x7800_0000) >> 20 == LFS_TYPE_CCRC {
+                if self.read_u32_le(block, off + 4)? != crc {
+                    break;
+                }
+                ptag ^= ((tag >> 20) & 1) << 31;
+                committed_tail = Some(tail);
+                crc = 0xffff_ffff;
+                continue;
+            }
+
+            crc = self.crc(block, off + 4, tag_dsize(tag) - 4, crc)?;
+            if (tag & 0x7000_0000) >> 20 == LFS_TYPE_TAIL {
+                tail = [
+                    self.read_u32_le(block, off + 4)?,
+                    self.read_u32_le(block, off + 8)?,
+                ];
+            }
+        }
+        Ok(committed_tail)
+    }
+}
+
+/// Append `suffix` to the last component of `path`, which must not be empty or end with a slash.
+fn with_suffix(path: &Path, suffix: &[u8]) -> Result<PathBuf> {
+    if path.is_empty() || path.as_str().ends_with('/') {
+        return Err(Error::INVALID);
+    }
+    let mut buf: heapless::Vec<u8, { PathBuf::MAX_SIZE }> = Default::default();
+    buf.extend_from_slice(path.as_str().as_bytes())
+        .and_then(|_| buf.extend_from_slice(suffix))
+        .map_err(|_| Error::FILENAME_TOO_LONG)?;
+    PathBuf::try_from(&buf[..]).map_err(|_| Error::FILENAME_TOO_LONG)
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // position of the last `*` in the pattern and of the name when it was reached
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, n));
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// How [`Filesystem::read_dir_since`][] handles entries without a timestamp attribute.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MissingTimestamp {
+    /// Treat the entry as newer than any timestamp.
+    Include,
+    /// Skip the entry.
+    Skip,
+}
+
+/// Configuration of a mounted filesystem, see [`Filesystem::stat`][].
+#[derive(Clone, Copy)]
+pub struct FsStat(ll::lfs_fsinfo);
+
+impl FsStat {
+    /// The on-disk version of the filesystem.
+    pub fn disk_version(&self) -> Version {
+        self.0.disk_version.into()
+    }
+
+    /// The size of a block in bytes.
+    pub fn block_size(&self) -> usize {
+        self.0.block_size as _
+    }
+
+    /// The number of blocks in the filesystem.
+    pub fn block_count(&self) -> usize {
+        self.0.block_count as _
+    }
+
+    /// The maximum length of a file name in bytes.
+    pub fn name_max(&self) -> usize {
+        self.0.name_max as _
+    }
+
+    /// The maximum size of a file in bytes.
+    pub fn file_max(&self) -> usize {
+        self.0.file_max as _
+    }
+
+    /// The maximum size of a custom attribute in bytes.
+    pub fn attr_max(&self) -> usize {
+        self.0.attr_max as _
+    }
+}
+
+impl fmt::Debug for FsStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FsStat")
+            .field("disk_version", &self.disk_version())
+            .field("block_size", &self.block_size())
+            .field("block_count", &self.block_count())
+            .field("name_max", &self.name_max())
+            .field("file_max", &self.file_max())
+            .field("attr_max", &self.attr_max())
+            .finish()
+    }
+}
+
+/// The littlefs configuration of a filesystem, see [`Filesystem::config_summary`][].
+///
+/// All sizes are in bytes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConfigSummary {
+    read_size: usize,
+    prog_size: usize,
+    block_size: usize,
+    block_count: usize,
+    block_cycles: isize,
+    cache_size: usize,
+    lookahead_size: usize,
+    name_max: usize,
+    file_max: usize,
+    attr_max: usize,
+}
+
+impl ConfigSummary {
+    fn new(config: &ll::lfs_config) -> Self {
+        Self {
+            read_size: config.read_size as _,
+            prog_size: config.prog_size as _,
+            block_size: config.block_size as _,
+            block_count: config.block_count as _,
+            block_cycles: config.block_cycles as _,
+            cache_size: config.cache_size as _,
+            lookahead_size: config.lookahead_size as _,
+            name_max: config.name_max as _,
+            file_max: config.file_max as _,
+            attr_max: config.attr_max as _,
+        }
+    }
+
+    /// The minimum size of a read.
+    pub fn read_size(&self) -> usize {
+        self.read_size
+    }
+
+    /// The minimum size of a write.
+    pub fn prog_size(&self) -> usize {
+        self.prog_size
+    }
+
+    /// The size of a block.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// The number of blocks.
+    pub fn block_count(&self) -> usize {
+        self.block_count
+    }
+
+    /// The number of erase cycles before metadata is moved, or -1 if wear-leveling is disabled.
+    pub fn block_cycles(&self) -> isize {
+        self.block_cycles
+    }
+
+    /// The size of the read, write and file caches.
+    pub fn cache_size(&self) -> usize {
+        self.cache_size
+    }
+
+    /// The size of the lookahead buffer.
+    pub fn lookahead_size(&self) -> usize {
+        self.lookahead_size
+    }
+
+    /// The maximum length of a file name.
+    pub fn name_max(&self) -> usize {
+        self.name_max
+    }
+
+    /// The maximum size of a file.
+    pub fn file_max(&self) -> usize {
+        self.file_max
+    }
+
+    /// The maximum size of a custom attribute.
+    pub fn attr_max(&self) -> usize {
+        self.attr_max
+    }
+}
+
+impl fmt::Display for ConfigSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "read_size={} prog_size={} block_size={} block_count={} block_cycles={} \
+             cache_size={} lookahead_size={} name_max={} file_max={} attr_max={}",
+            self.read_size,
+            self.prog_size,
+            self.block_size,
+            self.block_count,
+            self.block_cycles,
+            self.cache_size,
+            self.lookahead_size,
+            self.name_max,
+            self.file_max,
+            self.attr_max,
+        )
+    }
+}
+
+/// Buffers that are used by a filesystem mounted with [`Filesystem::mount_with_buffers`][].
+///
+/// - The read and the prog buffer must be at least `Storage::CACHE_SIZE` bytes long.  They
+///   don't have alignment requirements.
+/// - The lookahead buffer must be at least `Storage::LOOKAHEAD_SIZE` words long.  It is a slice
+///   of `u64` so that it is always sufficiently aligned.
+///
+/// littlefs keeps pointers to the buffers while the filesystem is mounted, so they are borrowed
+/// for the lifetime of the [`Filesystem`][].
+pub struct MountBuffers<'a> {
+    read: &'a mut [u8],
+    prog: &'a mut [u8],
+    lookahead: &'a mut [u64],
+}
+
+impl<'a> MountBuffers<'a> {
+    pub fn new(read: &'a mut [u8], prog: &'a mut [u8], lookahead: &'a mut [u64]) -> Self {
+        Self {
+            read,
+            prog,
+            lookahead,
+        }
+    }
+}
+
 // pub struct Filesystem<'alloc, 'storage, Storage: driver::Storage> {
 //     pub(crate) alloc: &'alloc mut Allocation<Storage>,
 //     pub(crate) storage: &'storage mut Storage,
@@ -180,9 +549,95 @@ impl<Storage: driver::Storage> Allocation<Storage> {
 
 // one lifetime is simpler than two... hopefully should be enough
 // also consider "erasing" the lifetime completely
+/// A mounted littlefs filesystem.
+///
+/// # Paths
+///
+/// All methods take paths as `&Path`.  A `&PathBuf` can be passed directly as it dereferences
+/// to `&Path`, and so can the result of the [`path!`](crate::path!) macro:
+///
+/// ```
+/// # use littlefs2::{fs::Filesystem, path, path::PathBuf, ram_storage};
+/// # ram_storage!(tiny);
+/// # let mut ram = Ram::default();
+/// # let mut storage = RamStorage::new(&mut ram);
+/// # Filesystem::format(&mut storage).unwrap();
+/// Filesystem::mount_and_then(&mut storage, |fs| {
+///     let path = PathBuf::from(path!("file"));
+///     fs.write(&path, b"data")?;
+///     assert!(fs.metadata(path!("file"))?.is_file());
+///     fs.rename(&path, path!("other"))?;
+///     fs.remove(&PathBuf::from(path!("other")))
+/// })
+/// .unwrap();
+/// ```
+///
+/// The methods are intentionally not generic over `impl AsRef<Path>`:  `Path` already
+/// implements `AsRef<str>`, so a second `AsRef` implementation would make existing calls of
+/// `path.as_ref()` ambiguous, and generic methods would be monomorphized for every path type,
+/// increasing the code size.
+///
+/// # Concurrency
+///
+/// littlefs is not reentrant, and `Filesystem` uses interior mutability without
+/// synchronization.  It is therefore neither `Send` nor `Sync`, so it cannot be shared between
+/// threads, tasks running on different executors or cores:
+///
+/// ```compile_fail
+/// # use littlefs2::{fs::Filesystem, ram_storage};
+/// # ram_storage!(tiny);
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<Filesystem<'_, RamStorage<'_>>>();
+/// ```
+///
+/// Taking a lock around each call into littlefs would not change this, as the Rust state of
+/// the filesystem and its open files would still be accessed concurrently.  To use the
+/// filesystem from multiple cores, keep it on one core and send requests to it, or put the
+/// storage into a mutex and mount the filesystem while holding the lock, for example with
+/// [`Filesystem::mount_and_then`][].  Circumventing these checks with `unsafe` code, for
+/// example by sharing a `Filesystem` through a raw pointer, is undefined behavior.
 pub struct Filesystem<'a, Storage: driver::Storage> {
     alloc: RefCell<&'a mut Allocation<Storage>>,
     storage: &'a mut Storage,
+    open_files: Cell<usize>,
+    cached_available_blocks: Cell<Option<usize>>,
+    #[cfg(feature = "handle-tracking")]
+    handles: RefCell<heapless::Vec<(usize, HandleKind, PathBuf), MAX_TRACKED_HANDLES>>,
+}
+
+/// Maximum number of handles reported by [`Filesystem::open_handles`][].
+#[cfg(feature = "handle-tracking")]
+const MAX_TRACKED_HANDLES: usize = 32;
+
+/// The kind of an open handle, see [`Filesystem::open_handles`][].
+#[cfg(feature = "handle-tracking")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HandleKind {
+    File,
+    Dir,
+}
+
+/// Creates a `DirEntry` for an entry of the directory `dir`.
+///
+/// If `PathBuf::MAX_SIZE` is reduced with a feature, the name or the full path of the entry may
+/// not fit into a `PathBuf`, which is reported as [`Error::FILENAME_TOO_LONG`][].
+fn dir_entry(dir: &Path, info: ll::lfs_info) -> Result<DirEntry> {
+    let len = info
+        .name
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(info.name.len());
+    if len > PathBuf::MAX_SIZE {
+        return Err(Error::FILENAME_TOO_LONG);
+    }
+    let mut name = [0; PathBuf::MAX_SIZE_PLUS_ONE];
+    name[..len].copy_from_slice(&info.name[..len]);
+    // littlefs names are nul-terminated and we checked the length
+    let file_name = unsafe { PathBuf::from_buffer_unchecked(name) };
+    let path = dir
+        .try_join(&file_name)
+        .map_err(|_| Error::FILENAME_TOO_LONG)?;
+    Ok(DirEntry::new(file_name, metadata(info), path))
 }
 
 fn metadata(info: ll::lfs_info) -> Metadata {
@@ -208,6 +663,7 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
     }
 
     pub fn format(storage: &mut Storage) -> Result<()> {
+        validate_geometry(storage)?;
         let alloc = &mut Allocation::new();
         let fs = Filesystem::new(alloc, storage);
         let mut alloc = fs.alloc.borrow_mut();
@@ -215,6 +671,51 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
         result_from((), return_code)
     }
 
+    /// Erase every block of the storage, then format it.
+    ///
+    /// Unlike [`Filesystem::format`][], this guarantees that no data from a previous filesystem
+    /// survives in unused blocks.  It is intended for secure provisioning, not for routine
+    /// formatting:  it calls [`Storage::erase`](driver::Storage::erase) once per block, which
+    /// can take a long time on large flash devices and causes a full erase cycle of wear.
+    pub fn format_erased(storage: &mut Storage) -> Result<()> {
+        for block in 0..Storage::BLOCK_COUNT {
+            Self::erase_block(storage, block)?;
+        }
+        Self::format(storage)
+    }
+
+    /// Erase a logical block, one erase unit of `Storage::ERASE_SIZE` bytes at a time.
+    fn erase_block(storage: &mut Storage, block: usize) -> Result<()> {
+        let start = block * Storage::BLOCK_SIZE;
+        for off in (start..start + Storage::BLOCK_SIZE).step_by(Storage::ERASE_SIZE) {
+            storage.erase(off, Storage::ERASE_SIZE)?;
+        }
+        Ok(())
+    }
+
+    /// Read the first `N` bytes of the two blocks of the superblock pair without mounting.
+    ///
+    /// The superblock is stored in the metadata pair in blocks 0 and 1.  Each block starts with
+    /// a 32-bit little-endian revision count followed by the metadata tags.  The first commit
+    /// contains the superblock entry with the magic string `littlefs` at offset 8, followed by
+    /// the little-endian superblock struct (disk version, block size, block count, name, file and
+    /// attribute limits).  The block with the higher revision count is the current one.  If a
+    /// commit was interrupted, the two copies may differ, so both are returned:  `[block 0,
+    /// block 1]`.
+    ///
+    /// `N` must not be larger than `Storage::BLOCK_SIZE` and must be a multiple of
+    /// `Storage::READ_SIZE`, otherwise [`Error::INVALID`][] is returned.
+    pub fn read_superblock<const N: usize>(storage: &mut Storage) -> Result<[[u8; N]; 2]> {
+        if N > Storage::BLOCK_SIZE || N % Storage::READ_SIZE != 0 {
+            return Err(Error::INVALID);
+        }
+        let mut blocks = [[0; N]; 2];
+        for (i, block) in blocks.iter_mut().enumerate() {
+            storage.read(i * Storage::BLOCK_SIZE, block)?;
+        }
+        Ok(blocks)
+    }
+
     // TODO: check if this is equivalent to `is_formatted`.
     pub fn is_mountable(storage: &mut Storage) -> bool {
         let alloc = &mut Allocation::new();
@@ -228,6 +729,27 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
         self.storage
     }
 
+    /// Returns a pointer to the littlefs state of the mounted filesystem.
+    ///
+    /// This is an escape hatch to call littlefs functions that are not wrapped by this crate.
+    ///
+    /// # Safety
+    ///
+    /// The pointer is valid as long as the filesystem is borrowed.  The caller must ensure that:
+    /// - the filesystem is not unmounted or formatted through the pointer, as `Filesystem`
+    ///   assumes that it stays mounted,
+    /// - the pointer is not used while a method of this filesystem or of a file or directory
+    ///   opened on it is running, for example from a callback,
+    /// - files and directories opened through the pointer are closed before the filesystem is
+    ///   dropped, and they are not tracked by [`Filesystem::open_file_count`][],
+    /// - the configuration and the buffers referenced by the state are not modified.
+    ///
+    /// Changes made through the pointer may invalidate the cached number of available blocks,
+    /// see [`Filesystem::available_blocks_cached`][].
+    pub unsafe fn raw(&self) -> *mut ll::lfs_t {
+        addr_of_mut!((*self.alloc.as_ptr()).state)
+    }
+
     /// This API avoids the need for using `Allocation`.
     pub fn mount_and_then<R>(
         storage: &mut Storage,
@@ -263,6 +785,70 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
             .map(|blocks| self.total_blocks().saturating_sub(blocks))
     }
 
+    /// Query the configuration of the mounted filesystem using `lfs_fs_stat`.
+    ///
+    /// The block size and the name, file and attribute limits are the values that littlefs uses
+    /// for the mounted filesystem.  They are read from the superblock if it contains them and
+    /// otherwise taken from the configuration derived from the [`Storage`](driver::Storage)
+    /// constants.  Mounting fails if the block size or the block count of the superblock don't
+    /// match the `Storage` constants, so these values normally agree with
+    /// [`total_blocks`](Filesystem::total_blocks) and `Storage::BLOCK_SIZE`.
+    pub fn stat(&self) -> Result<FsStat> {
+        let mut info: ll::lfs_fsinfo = unsafe { mem::MaybeUninit::zeroed().assume_init() };
+        let return_code = unsafe { ll::lfs_fs_stat(&mut self.alloc.borrow_mut().state, &mut info) };
+        result_from(FsStat(info), return_code)
+    }
+
+    /// Return the configuration that was passed to littlefs for this filesystem.
+    ///
+    /// Unlike [`Filesystem::stat`][], this does not access the storage.  It reports the values
+    /// of the configuration as constructed from the [`Storage`](driver::Storage) constants and
+    /// is intended for logging and debugging.
+    pub fn config_summary(&self) -> ConfigSummary {
+        ConfigSummary::new(&self.alloc.borrow().config)
+    }
+
+    /// Perform janitorial work with `lfs_fs_gc` to reduce the latency of later operations.
+    ///
+    /// Depending on the state of the filesystem, this finishes interrupted operations to make the
+    /// filesystem consistent, compacts metadata pairs that are mostly full and scans for free
+    /// blocks.  This work would otherwise be performed when needed by a later write, so calling
+    /// `gc` during idle time can avoid latency spikes.  It does not guarantee that a later
+    /// write does not trigger a compaction, and it does not free space that is still in use.
+    ///
+    /// This method writes to the storage if there is anything to do.  It is not necessary to
+    /// call it for correctness.
+    pub fn gc(&self) -> Result<()> {
+        let return_code = unsafe { ll::lfs_fs_gc(&mut self.alloc.borrow_mut().state) };
+        result_from((), return_code)
+    }
+
+    /// Available number of unused blocks in the filesystem, using a cached value if possible
+    ///
+    /// littlefs has no way to determine the number of unused blocks without traversing the
+    /// whole filesystem, so [`available_blocks`](struct.Filesystem.html#method.available_blocks)
+    /// can be slow.  This method returns the value computed by the last call of this method
+    /// unless the cache was cleared with
+    /// [`invalidate_available_blocks`](struct.Filesystem.html#method.invalidate_available_blocks).
+    ///
+    /// The cache is *not* updated when the filesystem is modified, so the returned value is
+    /// only an estimate.  It is intended for frequent checks, for example in a logging loop,
+    /// that invalidate the cache periodically or after a certain amount of data was written.
+    pub fn available_blocks_cached(&self) -> Result<usize> {
+        if let Some(blocks) = self.cached_available_blocks.get() {
+            return Ok(blocks);
+        }
+        let blocks = self.available_blocks()?;
+        self.cached_available_blocks.set(Some(blocks));
+        Ok(blocks)
+    }
+
+    /// Clear the value cached by
+    /// [`available_blocks_cached`](struct.Filesystem.html#method.available_blocks_cached).
+    pub fn invalidate_available_blocks(&self) {
+        self.cached_available_blocks.set(None);
+    }
+
     /// Available number of unused bytes in the filesystem
     ///
     /// This is a lower bound, more may be available. First, more blocks may be available as
@@ -369,6 +955,146 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
         result_from((), return_code)
     }
 
+    /// Rename or move a file or directory and return the metadata of the entry that was
+    /// replaced.
+    ///
+    /// If `to` exists, its metadata is queried before the rename and returned, otherwise `None`
+    /// is returned.  As `Filesystem` cannot be shared between threads, `to` cannot be modified
+    /// between the two operations.  If the rename fails, the error is returned.
+    pub fn rename_returning(&self, from: &Path, to: &Path) -> Result<Option<Metadata>> {
+        let replaced = match self.metadata(to) {
+            Ok(metadata) => Some(metadata),
+            Err(Error::NO_SUCH_ENTRY) => None,
+            Err(error) => return Err(error),
+        };
+        self.rename(from, to)?;
+        Ok(replaced)
+    }
+
+    /// Rename or move a file or directory, keeping the entry it replaces until the rename has
+    /// succeeded.
+    ///
+    /// If `to` exists, it is first renamed to the backup `<to>.bak` in the same directory.  Then
+    /// `from` is renamed to `to`.  If this fails, the backup is renamed back to `to` and the
+    /// error is returned.  Otherwise, the backup is removed, recursively if it is a directory.
+    /// Unlike [`Filesystem::rename`][], this can replace a non-empty directory.
+    ///
+    /// Each step is atomic, but the whole operation is not:  after a power loss, `to` may be
+    /// missing while `<to>.bak` still contains the previous entry, and it is up to the caller to
+    /// restore it.  An existing backup is never replaced, instead
+    /// [`Error::ENTRY_ALREADY_EXISTED`][] is returned.  If restoring the backup after a failed
+    /// rename fails too, that error is returned and the previous entry is left at `<to>.bak`.
+    ///
+    /// If `to` is empty or ends with a slash, [`Error::INVALID`][] is returned.  If the name of
+    /// the backup would be too long, [`Error::FILENAME_TOO_LONG`][] is returned.
+    pub fn rename_safe(&self, from: &Path, to: &Path) -> Result<()> {
+        let replaced = match self.metadata(to) {
+            Ok(metadata) => metadata,
+            Err(Error::NO_SUCH_ENTRY) => return self.rename(from, to),
+            Err(error) => return Err(error),
+        };
+        let backup = with_suffix(to, b".bak")?;
+        if self.exists(&backup) {
+            return Err(Error::ENTRY_ALREADY_EXISTED);
+        }
+        self.rename(to, &backup)?;
+
+        if let Err(error) = self.rename(from, to) {
+            self.rename(&backup, to)?;
+            return Err(error);
+        }
+        if replaced.is_dir() {
+            self.remove_dir_all(&backup)
+        } else {
+            self.remove(&backup)
+        }
+    }
+
+    /// Rename or move a file or directory, creating the parent directories of `to` if necessary.
+    ///
+    /// The parent directories are created with [`Filesystem::create_dir_all`][] before the
+    /// rename.  If the rename fails, the created directories are not removed.
+    pub fn rename_with_parents(&self, from: &Path, to: &Path) -> Result<()> {
+        if let Some(parent) = to.parent() {
+            self.create_dir_all(&parent)?;
+        }
+        self.rename(from, to)
+    }
+
+    /// Number of files that are currently open on this filesystem.
+    ///
+    /// Files opened with the closure-based APIs, for example [`Filesystem::open_file_and_then`][],
+    /// are counted while the closure runs.  Files opened with [`OpenOptions::open`][] are counted
+    /// until [`File::close`][] is called, so a non-zero count after all operations have finished
+    /// indicates a file that was never closed.
+    pub fn open_file_count(&self) -> usize {
+        self.open_files.get()
+    }
+
+    /// Call `f` with the path and the kind of every file and directory that is currently open.
+    ///
+    /// The path is the path that was used to open the handle.  Handles opened by the
+    /// closure-based APIs and by the methods of `Filesystem` are included while they are open.
+    /// At most 32 handles are tracked, handles that are opened while 32 others are open are
+    /// not reported.
+    ///
+    /// This method is only available with the `handle-tracking` feature.
+    #[cfg(feature = "handle-tracking")]
+    pub fn open_handles(&self, mut f: impl FnMut(&Path, HandleKind)) {
+        let mut i = 0;
+        // don't hold the borrow while calling f, which may open other handles
+        while let Some((_, kind, path)) = self.handles.borrow().get(i).cloned() {
+            f(&path, kind);
+            i += 1;
+        }
+    }
+
+    #[cfg(feature = "handle-tracking")]
+    fn track_handle(&self, id: usize, kind: HandleKind, path: &Path) {
+        self.handles.borrow_mut().push((id, kind, path.into())).ok();
+    }
+
+    #[cfg(feature = "handle-tracking")]
+    fn untrack_handle(&self, id: usize) {
+        let mut handles = self.handles.borrow_mut();
+        if let Some(index) = handles.iter().position(|(handle, _, _)| *handle == id) {
+            handles.remove(index);
+        }
+    }
+
+    /// Move all entries of the directory `from` into the directory `to` and remove `from`.
+    ///
+    /// If `to` does not exist, `from` is renamed to `to`.  Otherwise, every entry of `from` is
+    /// renamed into `to`.  Subdirectories that exist in both directories are merged recursively.
+    /// If a file already exists in `to`, it is replaced if `overwrite` is set, and otherwise
+    /// [`Error::ENTRY_ALREADY_EXISTED`][] is returned.
+    ///
+    /// Each entry is moved atomically, but the operation as a whole is not:  if it fails or is
+    /// interrupted by a power loss, some entries may already have been moved to `to` while the
+    /// others are still in `from`.  Calling `merge_dir` again completes the operation.
+    pub fn merge_dir(&self, from: &Path, to: &Path, overwrite: bool) -> Result<()> {
+        if !self.try_exists(to)? {
+            return self.rename(from, to);
+        }
+        self.read_dir_and_then(from, |read_dir| {
+            // skip "." and ".."
+            for entry in read_dir.skip(2) {
+                let entry = entry?;
+                let target = to.join(entry.file_name());
+                match self.metadata(&target) {
+                    Ok(metadata) if metadata.is_dir() && entry.file_type().is_dir() => {
+                        self.merge_dir(entry.path(), &target, overwrite)?;
+                    }
+                    Ok(_) if !overwrite => return Err(Error::ENTRY_ALREADY_EXISTED),
+                    Ok(_) | Err(Error::NO_SUCH_ENTRY) => self.rename(entry.path(), &target)?,
+                    Err(error) => return Err(error),
+                }
+            }
+            Ok(())
+        })?;
+        self.remove_dir(from)
+    }
+
     /// Check whether a file or directory exists at a path.
     ///
     /// This is equivalent to calling [`Filesystem::metadata`][] and checking for an `Ok` return
@@ -377,10 +1103,43 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
         self.metadata(path).is_ok()
     }
 
+    /// Check whether a file or directory exists at a path, returning other errors.
+    ///
+    /// In contrast to [`Filesystem::exists`][], this only returns `Ok(false)` if
+    /// [`Filesystem::metadata`][] fails with [`Error::NO_SUCH_ENTRY`][].  All other errors, for
+    /// example [`Error::CORRUPTION`][], are returned to the caller.
+    pub fn try_exists(&self, path: &Path) -> Result<bool> {
+        match self.metadata(path) {
+            Ok(_) => Ok(true),
+            Err(Error::NO_SUCH_ENTRY) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Return the deepest ancestor of `path` (including `path` itself) that exists.
+    ///
+    /// The ancestors are checked starting from the full path.  If none of them exists, the root
+    /// directory `/` is returned.  Errors other than [`Error::NO_SUCH_ENTRY`][] are returned to
+    /// the caller.
+    pub fn deepest_existing(&self, path: &Path) -> Result<PathBuf> {
+        for ancestor in path.ancestors() {
+            match self.metadata(&ancestor) {
+                Ok(_) => return Ok(ancestor),
+                Err(Error::NO_SUCH_ENTRY) => continue,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(crate::path!("/").into())
+    }
+
     /// Given a path, query the filesystem to get information about a file or directory.
     ///
     /// To read user attributes, use
     /// [`Filesystem::attribute`](struct.Filesystem.html#method.attribute)
+    ///
+    /// If there is no file or directory at `path`, [`Error::NO_SUCH_ENTRY`][] is returned.  Other
+    /// errors indicate that the filesystem could not be queried, for example because it is
+    /// corrupted.
     pub fn metadata(&self, path: &Path) -> Result<Metadata> {
         // do *not* not call assume_init here and pass into the unsafe block.
         // strange things happen ;)
@@ -411,6 +1170,21 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
         File::open_and_then(self, path, f)
     }
 
+    /// Open a file for appending, creating it if it does not exist, see [`File::append`][].
+    ///
+    /// Together with [`open_file_and_then`](Filesystem::open_file_and_then) (read-only) and
+    /// [`create_file_and_then`](Filesystem::create_file_and_then) (write, create and truncate),
+    /// this covers the common cases.  Use
+    /// [`open_file_with_options_and_then`](Filesystem::open_file_with_options_and_then) for
+    /// other combinations of options.
+    pub fn append_file_and_then<R>(
+        &self,
+        path: &Path,
+        f: impl FnOnce(&File<'_, '_, Storage>) -> Result<R>,
+    ) -> Result<R> {
+        File::append_and_then(self, path, f)
+    }
+
     pub fn with_options() -> OpenOptions {
         OpenOptions::new()
     }
@@ -426,6 +1200,11 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
     }
 
     /// Read attribute.
+    ///
+    /// If `buffer` is smaller than the attribute, only the first `buffer.len()` bytes are read.
+    /// The returned [`Attribute`][] still reports the size of the stored attribute in
+    /// [`Attribute::total_size`][], so truncation can be detected with
+    /// [`Attribute::is_truncated`][].
     pub fn attribute<'a>(
         &self,
         path: &Path,
@@ -465,7 +1244,68 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
         result_from((), return_code)
     }
 
+    /// Remove attribute if it is present.
+    ///
+    /// Returns `true` if the attribute was removed and `false` if it was not set.  Unlike
+    /// [`Filesystem::remove_attribute`][], this does not write to the storage if the attribute
+    /// is absent.  If `path` does not exist, [`Error::NO_SUCH_ENTRY`][] is returned.
+    pub fn remove_attribute_if_exists(&self, path: &Path, id: u8) -> Result<bool> {
+        if self.attribute(path, id, &mut [])?.is_none() {
+            return Ok(false);
+        }
+        self.remove_attribute(path, id)?;
+        Ok(true)
+    }
+
+    /// Copy all custom attributes of `from` to `to`, without touching their contents.
+    ///
+    /// littlefs cannot list the attributes of an entry, so every id from `0` to `255` is probed
+    /// on `from`, which takes 256 metadata lookups.  Each attribute that is present is written to
+    /// `to` with a separate commit, replacing an attribute with the same id.  Attributes of `to`
+    /// whose id is not set on `from` are kept.  `from` and `to` may be files or directories.
+    pub fn copy_attributes(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut buffer = [0; Attribute::MAX_SIZE as usize];
+        for id in 0..=u8::MAX {
+            if let Some(attribute) = self.attribute(from, id, &mut buffer)? {
+                self.set_attribute(to, id, attribute.data())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Truncate the file at `path` to length zero and remove all of its custom attributes.
+    ///
+    /// The file is opened with [`OpenOptions::truncate`][], the attributes are removed while it is
+    /// open and the truncation is committed when the file is closed, so the file handle is not
+    /// closed between the steps.  littlefs commits every change of an attribute separately,
+    /// however, so this is not atomic:  after a power loss, some attributes may already be
+    /// removed while the file still has its previous contents.  Only the attributes that are
+    /// present are removed, each with a separate commit.
+    ///
+    /// The file must exist, otherwise [`Error::NO_SUCH_ENTRY`][] is returned.
+    pub fn reset_file(&self, path: &Path) -> Result<()> {
+        OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open_and_then(self, path, |_| {
+                for id in 0..=u8::MAX {
+                    self.remove_attribute_if_exists(path, id)?;
+                }
+                Ok(())
+            })
+    }
+
     /// Set attribute.
+    ///
+    /// An entry can have up to 256 attributes, one for each id.  In practice, the number is
+    /// limited by the size of the metadata blocks:  all attributes of an entry, together with
+    /// its name and, for small files, its inline contents, must fit into one metadata block of
+    /// `Storage::BLOCK_SIZE` bytes, and each attribute needs four bytes in addition to its data.
+    /// If an attribute does not fit, [`Error::NO_SPACE`][] is returned and the attributes of the
+    /// entry are not changed.  [`Error::NO_SPACE`][] is also returned if `data` is longer than
+    /// [`Attribute::MAX_SIZE`][] or if the filesystem is full.  There is no fixed maximum
+    /// number of attributes that could be checked in advance, as the limit depends on the size
+    /// of the attributes and of the other data in the metadata block.
     pub fn set_attribute(&self, path: &Path, id: u8, data: &[u8]) -> Result<()> {
         let return_code = unsafe {
             ll::lfs_setattr(
@@ -480,6 +1320,26 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
         result_from((), return_code)
     }
 
+    /// Read a filesystem-wide attribute.
+    ///
+    /// littlefs does not have a separate namespace for attributes of the filesystem, so these
+    /// are stored as attributes of the root directory `/`.  They share the ids `0..=255` with
+    /// attributes set with [`Filesystem::set_attribute`][] on `/`, and they are limited to
+    /// [`Attribute::MAX_SIZE`][] bytes.
+    pub fn fs_attribute<'a>(&self, id: u8, buffer: &'a mut [u8]) -> Result<Option<Attribute<'a>>> {
+        self.attribute(crate::path!("/"), id, buffer)
+    }
+
+    /// Remove a filesystem-wide attribute, see [`Filesystem::fs_attribute`][].
+    pub fn remove_fs_attribute(&self, id: u8) -> Result<()> {
+        self.remove_attribute(crate::path!("/"), id)
+    }
+
+    /// Set a filesystem-wide attribute, see [`Filesystem::fs_attribute`][].
+    pub fn set_fs_attribute(&self, id: u8, data: &[u8]) -> Result<()> {
+        self.set_attribute(crate::path!("/"), id, data)
+    }
+
     /// C callback interface used by LittleFS to read data with the lower level system below the
     /// filesystem.
     extern "C" fn lfs_config_read(
@@ -524,9 +1384,8 @@ impl<Storage: driver::Storage> Filesystem<'_, Storage> {
     extern "C" fn lfs_config_erase(c: *const ll::lfs_config, block: ll::lfs_block_t) -> c_int {
         // println!("in lfs_config_erase");
         let storage = unsafe { &mut *((*c).context as *mut Storage) };
-        let off = block as usize * Storage::BLOCK_SIZE;
 
-        error_code_from(storage.erase(off, Storage::BLOCK_SIZE))
+        error_code_from(Self::erase_block(storage, block as usize))
     }
 
     /// C callback interface used by LittleFS to sync data with the lower level interface below the
@@ -559,10 +1418,84 @@ impl<S: driver::Storage> FileAllocation<S> {
     }
 }
 
-pub struct File<'a, 'b, S: driver::Storage> {
-    // We must store a raw pointer here since the FFI retains a copy of a pointer
-    // to the field alloc.state, so we cannot assert unique mutable access.
-    alloc: RefCell<*mut FileAllocation<S>>,
+/// An open file.
+///
+/// # Closing
+///
+/// Written data is cached by littlefs and only committed to the storage when the file is
+/// synced or closed.  `File` does not implement `Drop`:  the closure-based APIs like
+/// [`Filesystem::open_file_and_then`][] close the file after the closure returns and return the
+/// error of [`File::close`][] if closing fails, so a failed flush is never lost.  If the closure
+/// itself fails, its error is returned instead.  Call [`File::sync`][] inside the closure to
+/// handle a flush failure before the file is closed.  A file opened with the unsafe
+/// [`OpenOptions::open`][] must be closed with [`File::close`][], which also returns the error
+/// of the final sync.
+///
+/// # Generic I/O
+///
+/// `File` implements [`io::Read`][], [`io::Write`][] and [`io::Seek`][], so it can be passed to
+/// code that is generic over these traits, for example a parser for an archive format that
+/// needs to jump to the entries listed in a table of contents:
+///
+/// ```
+/// # use littlefs2::{fs::Filesystem, io::{Read, Result, Seek, SeekFrom}, path, ram_storage};
+/// # ram_storage!(tiny);
+/// /// Reads the `u32` stored at the offset given by the first four bytes.
+/// fn read_indirect(reader: &(impl Read + Seek)) -> Result<u32> {
+///     let mut buf = [0; 4];
+///     reader.read_exact(&mut buf)?;
+///     reader.seek(SeekFrom::Start(u32::from_le_bytes(buf)))?;
+///     reader.read_exact(&mut buf)?;
+///     Ok(u32::from_le_bytes(buf))
+/// }
+///
+/// # let mut ram = Ram::default();
+/// # let mut storage = RamStorage::new(&mut ram);
+/// # Filesystem::format(&mut storage).unwrap();
+/// Filesystem::mount_and_then(&mut storage, |fs| {
+///     fs.write(path!("archive"), &[8, 0, 0, 0, 0, 0, 0, 0, 42, 0, 0, 0])?;
+///     let value = fs.open_file_and_then(path!("archive"), |file| read_indirect(file))?;
+///     assert_eq!(value, 42);
+///     Ok(())
+/// })
+/// .unwrap();
+/// ```
+///
+/// # Opening a file multiple times
+///
+/// A file can be opened multiple times at once, for example by a logger that appends to it and
+/// a reader.  Each `File` keeps its own view of the file contents as they were when it was
+/// opened:  changes written through one `File` are not visible through another one that is
+/// already open, not even after they have been synced or the writing `File` has been closed.
+/// To read the changes, close the reading `File` and open the file again after syncing the
+/// writing `File`.  Data blocks that are no longer part of the file after a sync can be reused
+/// by later writes, so reading old contents through a `File` that was opened before should be
+/// avoided.
+///
+/// # Sending files between tasks
+///
+/// A `File` is neither `Send` nor `Sync`:  it borrows the [`Filesystem`][] it was opened on,
+/// which uses interior mutability without synchronization, and littlefs keeps a pointer to the
+/// file state in its list of open files.  Moving an open file to another task could therefore
+/// race with other operations on the same filesystem, or invalidate that pointer.
+///
+/// ```compile_fail
+/// # use littlefs2::{fs::File, ram_storage};
+/// # ram_storage!(tiny);
+/// fn assert_send<T: Send>() {}
+/// assert_send::<File<'_, '_, RamStorage<'_>>>();
+/// ```
+///
+/// To continue working on a file in another task, record its path and position, as returned by
+/// `seek(SeekFrom::Current(0))`, close it, hand the storage (or the mounted filesystem together
+/// with its storage, if the executor guarantees exclusive access) to the other task and reopen
+/// the file there, seeking to the recorded position.  The `Storage` implementation
+/// itself must be `Send` for this, and it must not be accessed by any other task while the
+/// filesystem is mounted.
+pub struct File<'a, 'b, S: driver::Storage> {
+    // We must store a raw pointer here since the FFI retains a copy of a pointer
+    // to the field alloc.state, so we cannot assert unique mutable access.
+    alloc: RefCell<*mut FileAllocation<S>>,
     fs: &'b Filesystem<'a, S>,
 }
 
@@ -582,6 +1515,11 @@ impl<'a, 'b, Storage: driver::Storage> File<'a, 'b, Storage> {
         OpenOptions::new()
     }
 
+    /// Open a file in read-only mode.
+    ///
+    /// littlefs does not track access times, so opening a file read-only, reading from it and
+    /// closing it never writes to or erases the storage.  The same applies to mounting the
+    /// filesystem and reading directories and metadata.
     pub unsafe fn open(
         fs: &'b Filesystem<'a, Storage>,
         alloc: &'b mut FileAllocation<Storage>,
@@ -622,6 +1560,34 @@ impl<'a, 'b, Storage: driver::Storage> File<'a, 'b, Storage> {
             .open_and_then(fs, path, f)
     }
 
+    /// Open a file for appending, creating it if it does not exist.
+    ///
+    /// All writes are appended to the end of the file, regardless of the cursor position.
+    pub unsafe fn append(
+        fs: &'b Filesystem<'a, Storage>,
+        alloc: &'b mut FileAllocation<Storage>,
+        path: &Path,
+    ) -> Result<Self> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(true)
+            .open(fs, alloc, path)
+    }
+
+    /// (Hopefully) safe abstraction around `append`.
+    pub fn append_and_then<R>(
+        fs: &Filesystem<'a, Storage>,
+        path: &Path,
+        f: impl FnOnce(&File<'_, '_, Storage>) -> Result<R>,
+    ) -> Result<R> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(true)
+            .open_and_then(fs, path, f)
+    }
+
     // Safety-hatch to experiment with missing parts of API
     pub unsafe fn borrow_filesystem<'c>(&'c mut self) -> &'c Filesystem<'a, Storage> {
         self.fs
@@ -630,8 +1596,14 @@ impl<'a, 'b, Storage: driver::Storage> File<'a, 'b, Storage> {
     /// Sync the file and drop it from the internal linked list.
     /// Not doing this is UB, which is why we have all the closure-based APIs.
     ///
+    /// If syncing fails, the error is returned, but the file is closed nonetheless.
+    ///
     /// This must not be called twice.
     pub unsafe fn close(self) -> Result<()> {
+        // littlefs removes the file from its list of open files even if closing fails
+        self.fs.open_files.set(self.fs.open_files.get() - 1);
+        #[cfg(feature = "handle-tracking")]
+        self.fs.untrack_handle(*self.alloc.borrow() as usize);
         let return_code = ll::lfs_file_close(
             &mut self.fs.alloc.borrow_mut().state,
             // We need to use addr_of_mut! here instead of & mut since
@@ -643,6 +1615,9 @@ impl<'a, 'b, Storage: driver::Storage> File<'a, 'b, Storage> {
     }
 
     /// Synchronize file contents to storage.
+    ///
+    /// After this call, the data written so far is committed and survives a power loss, and
+    /// files opened afterwards see it.  [`io::Write::flush`][] calls this method.
     pub fn sync(&self) -> Result<()> {
         let return_code = unsafe {
             // We need to use addr_of_mut! here instead of & mut since
@@ -656,6 +1631,39 @@ impl<'a, 'b, Storage: driver::Storage> File<'a, 'b, Storage> {
         result_from((), return_code)
     }
 
+    /// Write `data`, sync the file and read the data back to verify it.
+    ///
+    /// After writing and syncing, the cursor is moved back to the start of the written data,
+    /// the data is read again in small chunks and compared to `data`.  If it differs,
+    /// [`Error::CORRUPTION`][] is returned.  On success, the cursor is positioned after the
+    /// written data.  The file must be opened for reading and writing.
+    ///
+    /// This doubles the I/O of the write and adds a sync, so it should only be used for
+    /// critical data.  littlefs already reads back every program when flushing its caches
+    /// (`lfs_bd_flush`), so writes that are dropped or programmed incorrectly are detected
+    /// during the write itself.  This method additionally detects data that reads back
+    /// differently after the sync, for example because of bit flips or read disturbance.  As
+    /// the data is read back through littlefs, parts of it may be served from its caches
+    /// instead of the storage.
+    pub fn write_verified(&self, data: &[u8]) -> Result<()> {
+        use io::Write;
+
+        self.write_all(data)?;
+        self.sync()?;
+        let end = self.seek(io::SeekFrom::Current(0))?;
+        let start = end - data.len();
+        self.seek(io::SeekFrom::Start(start as u32))?;
+        let mut buf = [0; 64];
+        for expected in data.chunks(buf.len()) {
+            let buf = &mut buf[..expected.len()];
+            io::Read::read_exact(self, buf)?;
+            if buf != expected {
+                return Err(Error::CORRUPTION);
+            }
+        }
+        Ok(())
+    }
+
     /// Size of the file in bytes.
     pub fn len(&self) -> Result<usize> {
         let return_code = unsafe {
@@ -674,12 +1682,34 @@ impl<'a, 'b, Storage: driver::Storage> File<'a, 'b, Storage> {
         self.len().map(|l| l == 0)
     }
 
+    /// Read into `buf` without moving the cursor.
+    ///
+    /// This reads from the current position and then seeks back to it, for example to inspect
+    /// magic bytes before deciding how to parse a file.  It is not atomic:  if the seek fails,
+    /// the cursor is left after the bytes that were read.
+    pub fn peek(&self, buf: &mut [u8]) -> Result<usize> {
+        let position = self.seek(io::SeekFrom::Current(0))?;
+        let n = self.read(buf)?;
+        self.seek(io::SeekFrom::Start(position as u32))?;
+        Ok(n)
+    }
+
+    // littlefs only asserts that the file is writable, and assertions are disabled
+    fn check_writable(&self) -> Result<()> {
+        let flags = unsafe { (*(*self.alloc.borrow())).state.flags };
+        if !FileOpenFlags::from_bits_truncate(flags as _).contains(FileOpenFlags::WRITE) {
+            return Err(Error::READ_ONLY);
+        }
+        Ok(())
+    }
+
     /// Truncates or extends the underlying file, updating the size of this file to become size.
     ///
     /// If the size is less than the current file's size, then the file will be shrunk. If it is
     /// greater than the current file's size, then the file will be extended to size and have all
     /// of the intermediate data filled in with 0s.
     pub fn set_len(&self, size: usize) -> Result<()> {
+        self.check_writable()?;
         let return_code = unsafe {
             // We need to use addr_of_mut! here instead of & mut since
             // the FFI stores a copy of a pointer to the field state,
@@ -713,13 +1743,347 @@ impl<'a, 'b, Storage: driver::Storage> File<'a, 'b, Storage> {
         <Self as io::Read>::read(self, buf)
     }
 
+    /// Read a value of type `T` from the current position.
+    ///
+    /// Exactly `size_of::<T>()` bytes are read and reinterpreted as `T`, see
+    /// [`FromBytes`](io::FromBytes) for the byte order.  If the file ends before enough bytes
+    /// could be read, [`Error::IO`][] is returned like for
+    /// [`Read::read_exact`](io::Read::read_exact).
+    pub fn read_struct<T: io::FromBytes>(&self) -> Result<T> {
+        let mut value = mem::MaybeUninit::<T>::zeroed();
+        // SAFETY: the buffer covers exactly the (zero-initialized) value
+        let buf = unsafe {
+            slice::from_raw_parts_mut(value.as_mut_ptr().cast::<u8>(), mem::size_of::<T>())
+        };
+        io::Read::read_exact(self, buf)?;
+        // SAFETY: T: FromBytes is valid for any bytes
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Stream the bytes `start..start + len` of the file through `update`, like
+    /// [`Filesystem::hash_file`][].
+    ///
+    /// The range is clamped to the end of the file: if it extends past the end, only the bytes
+    /// up to the end are passed to `update`, and if `start` is at or past the end, `update` is
+    /// not called.  Returns the number of bytes passed to `update`, so a hash can be resumed at
+    /// `start + n` later, for example in the next boot session with the offset and the state of
+    /// the hash stored in attributes.  If bytes were read, the position of the file is left
+    /// after them.
+    pub fn hash_range(
+        &self,
+        start: usize,
+        len: usize,
+        mut update: impl FnMut(&[u8]),
+    ) -> Result<usize> {
+        let end = cmp::min(start.saturating_add(len), self.len()?);
+        if start >= end {
+            return Ok(0);
+        }
+        self.seek(io::SeekFrom::Start(start as u32))?;
+        let mut buf = Bytes::<Storage::CACHE_SIZE>::default();
+        let mut total = 0;
+        while total < end - start {
+            let n = cmp::min(buf.len(), end - start - total);
+            let n = self.read(&mut buf[..n])?;
+            if n == 0 {
+                break;
+            }
+            update(&buf[..n]);
+            total += n;
+        }
+        Ok(total)
+    }
+
+    /// Read into a possibly uninitialized buffer and return the initialized part of it.
+    ///
+    /// This avoids zeroing the buffer before reading, which can be noticeable for large buffers
+    /// on slow cores.  littlefs only writes to `buf` and never reads from it, so the first `n`
+    /// bytes are initialized after reading `n` bytes.  The returned slice contains these bytes,
+    /// the rest of `buf` is left unchanged.
+    pub fn read_uninit<'c>(&self, buf: &'c mut [mem::MaybeUninit<u8>]) -> Result<&'c mut [u8]> {
+        let return_code = unsafe {
+            // We need to use addr_of_mut! here instead of & mut since
+            // the FFI stores a copy of a pointer to the field state,
+            // so we cannot assert unique mutable access.
+            ll::lfs_file_read(
+                &mut self.fs.alloc.borrow_mut().state,
+                addr_of_mut!((*(*self.alloc.borrow_mut())).state),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len() as u32,
+            )
+        };
+        let n = u32_result(return_code)? as usize;
+        let initialized = &mut buf[..n];
+        // SAFETY: lfs_file_read initialized the first n bytes
+        Ok(unsafe { &mut *(initialized as *mut [mem::MaybeUninit<u8>] as *mut [u8]) })
+    }
+
     pub fn seek(&self, pos: io::SeekFrom) -> Result<usize> {
         <Self as io::Seek>::seek(self, pos)
     }
 
+    /// Write data to the file at the current position.
+    ///
+    /// If the filesystem is full, [`Error::NO_SPACE`][] is returned, so it can be distinguished
+    /// from I/O errors of the storage.  As littlefs caches written data, this error can also be
+    /// returned by a later write, [`sync`](File::sync) or [`close`](File::close).
     pub fn write(&self, buf: &[u8]) -> Result<usize> {
         <Self as io::Write>::write(self, buf)
     }
+
+    /// Returns an iterator over the bytes of the file, starting at the current position.
+    ///
+    /// The file is read through a [`BufReader`](io::BufReader) with a buffer of 32 bytes, so
+    /// littlefs is not called for every byte.  As the buffer reads ahead, the position of the
+    /// file is generally not the position of the last byte that was yielded.  If an error
+    /// occurs, it is yielded and the iteration ends.
+    pub fn bytes(&self) -> io::Bytes<&Self, 32> {
+        io::BufReader::new(self).bytes()
+    }
+
+    /// Copies data from the current position of this file to the current position of `dst`.
+    ///
+    /// At most `len` bytes are copied, or all data until the end of this file if `len` is
+    /// `None`.  The data is transferred through a stack buffer of `Storage::CACHE_SIZE` bytes.
+    /// Returns the number of bytes copied.
+    pub fn copy_to(&self, dst: &File<'_, '_, Storage>, len: Option<usize>) -> Result<usize> {
+        use io::Write;
+
+        let mut buf = Bytes::<Storage::CACHE_SIZE>::default();
+        let mut copied = 0;
+        loop {
+            let n = match len {
+                Some(len) => cmp::min(buf.len(), len - copied),
+                None => buf.len(),
+            };
+            if n == 0 {
+                break;
+            }
+            let read = self.read(&mut buf[..n])?;
+            if read == 0 {
+                break;
+            }
+            dst.write_all(&buf[..read])?;
+            copied += read;
+        }
+        Ok(copied)
+    }
+
+    /// Reads the file in chunks of `N` bytes, starting at the current position.
+    ///
+    /// Every chunk except for the last one is exactly `N` bytes long.  The chunks are read into
+    /// a buffer of `N` bytes stored in the returned [`Chunks`][].  As each chunk borrows this
+    /// buffer, `Chunks` does not implement `Iterator`.  Use a `while let` loop instead:
+    ///
+    /// ```
+    /// # use littlefs2::{fs::{Allocation, Filesystem}, path, ram_storage};
+    /// # ram_storage!(tiny);
+    /// # let mut ram = Ram::default();
+    /// # let mut storage = RamStorage::new(&mut ram);
+    /// # Filesystem::format(&mut storage).unwrap();
+    /// # let mut alloc = Allocation::new();
+    /// # let fs = Filesystem::mount(&mut alloc, &mut storage).unwrap();
+    /// # fs.write(path!("file"), &[1; 600]).unwrap();
+    /// fs.open_file_and_then(path!("file"), |file| {
+    ///     let mut chunks = file.chunks::<256>();
+    ///     let mut sum = 0u32;
+    ///     while let Some(chunk) = chunks.next() {
+    ///         sum += chunk?.iter().map(|b| u32::from(*b)).sum::<u32>();
+    ///     }
+    ///     assert_eq!(sum, 600);
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn chunks<const N: usize>(&self) -> Chunks<'_, 'a, 'b, Storage, N> {
+        Chunks {
+            file: self,
+            buf: [0; N],
+            done: false,
+        }
+    }
+
+    /// Returns a view of the byte range `[offset, offset + len)` of this file.
+    ///
+    /// Positions of the returned [`SubFile`][] are relative to `offset`.  Reads stop at the end
+    /// of the region, and writes that would extend past it fail with
+    /// [`Error::FILE_TOO_BIG`][] without writing anything.
+    ///
+    /// The view shares the cursor of the underlying file, so every operation on the view seeks
+    /// the file first.
+    pub fn subview(&self, offset: usize, len: usize) -> SubFile<'_, 'a, 'b, Storage> {
+        SubFile {
+            file: self,
+            offset,
+            len,
+            pos: Cell::new(0),
+        }
+    }
+}
+
+/// Reads a file in chunks of `N` bytes.
+///
+/// See [`File::chunks`][].
+pub struct Chunks<'f, 'a, 'b, S: driver::Storage, const N: usize> {
+    file: &'f File<'a, 'b, S>,
+    buf: [u8; N],
+    done: bool,
+}
+
+impl<S: driver::Storage, const N: usize> Chunks<'_, '_, '_, S, N> {
+    /// Reads the next chunk.
+    ///
+    /// Returns `None` once the end of the file has been reached or an error was returned.  The
+    /// returned slice borrows the internal buffer, so it is only valid until the next call.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<&[u8]>> {
+        if self.done {
+            return None;
+        }
+        let mut filled = 0;
+        while filled < N {
+            match self.file.read(&mut self.buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        if filled < N {
+            self.done = true;
+        }
+        if filled == 0 {
+            return None;
+        }
+        Some(Ok(&self.buf[..filled]))
+    }
+}
+
+/// A view of a byte range of a [`File`][].
+///
+/// See [`File::subview`][].
+pub struct SubFile<'f, 'a, 'b, S: driver::Storage> {
+    file: &'f File<'a, 'b, S>,
+    offset: usize,
+    len: usize,
+    pos: Cell<usize>,
+}
+
+impl<S: driver::Storage> SubFile<'_, '_, '_, S> {
+    /// Size of the region in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn seek_file(&self) -> Result<()> {
+        let pos = u32::try_from(self.offset + self.pos.get()).map_err(|_| Error::INVALID)?;
+        self.file.seek(io::SeekFrom::Start(pos))?;
+        Ok(())
+    }
+}
+
+impl<S: driver::Storage> io::Read for SubFile<'_, '_, '_, S> {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos.get());
+        let n = cmp::min(buf.len(), remaining);
+        if n == 0 {
+            return Ok(0);
+        }
+        self.seek_file()?;
+        let read = self.file.read(&mut buf[..n])?;
+        self.pos.set(self.pos.get() + read);
+        Ok(read)
+    }
+}
+
+impl<S: driver::Storage> io::Seek for SubFile<'_, '_, '_, S> {
+    fn seek(&self, pos: io::SeekFrom) -> Result<usize> {
+        let base = match pos {
+            io::SeekFrom::Start(_) => 0,
+            io::SeekFrom::End(_) => self.len,
+            io::SeekFrom::Current(_) => self.pos.get(),
+        };
+        let off = match pos {
+            io::SeekFrom::Start(off) => off as isize,
+            io::SeekFrom::End(off) | io::SeekFrom::Current(off) => off as isize,
+        };
+        let new_pos = base
+            .checked_add_signed(off)
+            .filter(|new_pos| *new_pos <= self.len)
+            .ok_or(Error::INVALID)?;
+        self.pos.set(new_pos);
+        Ok(new_pos)
+    }
+}
+
+impl<S: driver::Storage> io::Write for SubFile<'_, '_, '_, S> {
+    fn write(&self, data: &[u8]) -> Result<usize> {
+        if self.pos.get() + data.len() > self.len {
+            return Err(Error::FILE_TOO_BIG);
+        }
+        if data.is_empty() {
+            return Ok(0);
+        }
+        self.seek_file()?;
+        let written = self.file.write(data)?;
+        self.pos.set(self.pos.get() + written);
+        Ok(written)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.file.sync()
+    }
+}
+
+/// A user attribute that is read and written together with a file, see [`FileConfig`][].
+///
+/// If the file is opened for reading, the attribute is read into the buffer when the file is
+/// opened.  If the attribute does not exist, the buffer is not modified.  If the file is opened
+/// for writing, the content of the buffer is written to the attribute when the file is synced
+/// or closed.  The buffer may be at most [`Attribute::MAX_SIZE`][] bytes long.
+#[repr(transparent)]
+pub struct FileAttr<'c> {
+    attr: ll::lfs_attr,
+    buffer: PhantomData<&'c mut [u8]>,
+}
+
+impl<'c> FileAttr<'c> {
+    pub fn new(id: u8, buffer: &'c mut [u8]) -> Self {
+        Self {
+            attr: ll::lfs_attr {
+                type_: id,
+                buffer: buffer.as_mut_ptr().cast(),
+                size: buffer.len() as _,
+            },
+            buffer: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        self.attr.type_
+    }
+}
+
+/// Buffers that are used by a file opened with [`OpenOptions::open_cfg`][].
+///
+/// The cache must be at least `Storage::CACHE_SIZE` bytes long.  littlefs keeps pointers to
+/// the cache and the attributes until the file is closed, so they are borrowed for the lifetime
+/// of the [`File`][].
+pub struct FileConfig<'c> {
+    cache: &'c mut [u8],
+    attrs: &'c mut [FileAttr<'c>],
+}
+
+impl<'c> FileConfig<'c> {
+    pub fn new(cache: &'c mut [u8], attrs: &'c mut [FileAttr<'c>]) -> Self {
+        Self { cache, attrs }
+    }
 }
 
 /// Options and flags which can be used to configure how a file is opened.
@@ -757,6 +2121,59 @@ impl OpenOptions {
         path: &Path,
     ) -> Result<File<'a, 'b, S>> {
         alloc.config.buffer = alloc.cache.get() as *mut _;
+        // the allocation might have been used with open_cfg before
+        alloc.config.attrs = core::ptr::null_mut();
+        alloc.config.attr_count = 0;
+        // We need to use addr_of_mut! here instead of & mut since
+        // the FFI stores a copy of a pointer to the field state,
+        // so we cannot assert unique mutable access.
+        let return_code = ll::lfs_file_opencfg(
+            &mut fs.alloc.borrow_mut().state,
+            addr_of_mut!(alloc.state),
+            path.as_ptr(),
+            self.0.bits(),
+            addr_of!(alloc.config),
+        );
+
+        let file = File {
+            alloc: RefCell::new(alloc),
+            fs,
+        };
+
+        let file = result_from(file, return_code)?;
+        fs.open_files.set(fs.open_files.get() + 1);
+        #[cfg(feature = "handle-tracking")]
+        fs.track_handle(*file.alloc.borrow() as usize, HandleKind::File, path);
+        Ok(file)
+    }
+
+    /// Open the file with the options previously specified, using the buffers and attributes
+    /// provided in `cfg`.
+    ///
+    /// In contrast to [`OpenOptions::open`][], the cache of `alloc` is not used, so it can be
+    /// placed in a static buffer.  The attributes in `cfg` are read when the file is opened and
+    /// written when it is synced or closed, see [`FileAttr`][].  If the cache is smaller than
+    /// `Storage::CACHE_SIZE` or if an attribute buffer is longer than the configured
+    /// [`attr_max`](FsStat::attr_max), [`Error::INVALID`][] is returned.
+    ///
+    /// The same safety considerations as for [`OpenOptions::open`][] apply.
+    pub unsafe fn open_cfg<'a, 'b, 'c: 'b, S: driver::Storage>(
+        &self,
+        fs: &'b Filesystem<'a, S>,
+        alloc: &mut FileAllocation<S>,
+        path: &Path,
+        cfg: FileConfig<'c>,
+    ) -> Result<File<'a, 'b, S>> {
+        if cfg.cache.len() < S::CACHE_SIZE::USIZE {
+            return Err(Error::INVALID);
+        }
+        let attr_max = fs.alloc.borrow().config.attr_max;
+        if cfg.attrs.iter().any(|attr| attr.attr.size > attr_max) {
+            return Err(Error::INVALID);
+        }
+        alloc.config.buffer = cfg.cache.as_mut_ptr().cast();
+        alloc.config.attrs = cfg.attrs.as_mut_ptr().cast();
+        alloc.config.attr_count = cfg.attrs.len() as _;
         // We need to use addr_of_mut! here instead of & mut since
         // the FFI stores a copy of a pointer to the field state,
         // so we cannot assert unique mutable access.
@@ -773,7 +2190,26 @@ impl OpenOptions {
             fs,
         };
 
-        result_from(file, return_code)
+        let file = result_from(file, return_code)?;
+        fs.open_files.set(fs.open_files.get() + 1);
+        #[cfg(feature = "handle-tracking")]
+        fs.track_handle(*file.alloc.borrow() as usize, HandleKind::File, path);
+        Ok(file)
+    }
+
+    /// (Hopefully) safe abstraction around `open_cfg`.
+    pub fn open_cfg_and_then<'a, R, S: driver::Storage>(
+        &self,
+        fs: &Filesystem<'a, S>,
+        path: &Path,
+        cfg: FileConfig<'_>,
+        f: impl FnOnce(&File<'a, '_, S>) -> Result<R>,
+    ) -> Result<R> {
+        let mut alloc = FileAllocation::new();
+        let mut file = unsafe { self.open_cfg(fs, &mut alloc, path, cfg)? };
+        let res = f(&mut file);
+        unsafe { file.close()? };
+        res
     }
 
     /// (Hopefully) safe abstraction around `open`.
@@ -845,6 +2281,11 @@ impl OpenOptions {
         self
     }
 
+    /// Truncate an existing file to length 0 when it is opened.
+    ///
+    /// This is independent of [`OpenOptions::create`][]:  if `create` is not set, opening a
+    /// file that does not exist fails with [`Error::NO_SUCH_ENTRY`][].  The file must be opened
+    /// for writing.
     pub fn truncate(&mut self, truncate: bool) -> &mut Self {
         if truncate {
             self.0.insert(FileOpenFlags::TRUNCATE)
@@ -897,6 +2338,7 @@ impl<S: driver::Storage> io::Seek for File<'_, '_, S> {
 
 impl<S: driver::Storage> io::Write for File<'_, '_, S> {
     fn write(&self, buf: &[u8]) -> Result<usize> {
+        self.check_writable()?;
         let return_code = unsafe {
             // We need to use addr_of_mut! here instead of & mut since
             // the FFI stores a copy of a pointer to the field state,
@@ -911,8 +2353,9 @@ impl<S: driver::Storage> io::Write for File<'_, '_, S> {
         u32_result(return_code).map(|n| n as usize)
     }
 
+    /// Commits the written data to the storage, same as [`File::sync`][].
     fn flush(&self) -> Result<()> {
-        Ok(())
+        self.sync()
     }
 }
 
@@ -932,20 +2375,28 @@ impl ReadDirAllocation {
     }
 }
 
+/// Iterates over the entries of a directory.
+///
+/// If reading the directory fails, for example with [`Error::CORRUPTION`][] if its metadata is
+/// corrupt, the error is yielded once and the iteration ends.  After the iterator returned
+/// `None`, it always returns `None`.
 pub struct ReadDir<'a, 'b, S: driver::Storage> {
     // We must store a raw pointer here since the FFI retains a copy of a pointer
     // to the field alloc.state, so we cannot assert unique mutable access.
     alloc: RefCell<*mut ReadDirAllocation>,
     fs: &'b Filesystem<'a, S>,
     path: &'b Path,
+    finished: bool,
 }
 
 impl<S: driver::Storage> Iterator for ReadDir<'_, '_, S> {
     type Item = Result<DirEntry>;
 
-    // remove this allowance again, once path overflow is properly handled
-    #[allow(unreachable_code)]
     fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
         let mut info: ll::lfs_info = unsafe { mem::MaybeUninit::zeroed().assume_init() };
         // We need to use addr_of_mut! here instead of & mut since
         // the FFI stores a copy of a pointer to the field state,
@@ -959,15 +2410,15 @@ impl<S: driver::Storage> Iterator for ReadDir<'_, '_, S> {
         };
 
         if return_code > 0 {
-            let file_name = unsafe { PathBuf::from_buffer_unchecked(info.name) };
-            let metadata = metadata(info);
-
-            let path = self.path.join(&file_name);
-
-            let dir_entry = DirEntry::new(file_name, metadata, path);
-            return Some(Ok(dir_entry));
+            let entry = dir_entry(self.path, info);
+            if entry.is_err() {
+                self.finished = true;
+            }
+            return Some(entry);
         }
 
+        self.finished = true;
+
         if return_code == 0 {
             return None;
         }
@@ -976,6 +2427,127 @@ impl<S: driver::Storage> Iterator for ReadDir<'_, '_, S> {
     }
 }
 
+impl<S: driver::Storage> FusedIterator for ReadDir<'_, '_, S> {}
+
+impl<'a, 'b, S: driver::Storage> ReadDir<'a, 'b, S> {
+    /// Collect the paths of the remaining entries, not including `.` and `..`.
+    ///
+    /// If there are more than `N` entries, [`Error::NO_MEMORY`][] is returned after reading the
+    /// entry that did not fit.  Use [`collect_paths_truncated`](ReadDir::collect_paths_truncated)
+    /// to keep the first `N` entries instead.
+    pub fn collect_paths<const N: usize>(&mut self) -> Result<heapless::Vec<PathBuf, N>> {
+        let mut paths = heapless::Vec::new();
+        for entry in self {
+            let entry = entry?;
+            if !matches!(entry.file_name().as_str(), "." | "..") {
+                paths
+                    .push(entry.path().into())
+                    .map_err(|_| Error::NO_MEMORY)?;
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Collect the paths of the remaining entries like
+    /// [`collect_paths`](ReadDir::collect_paths), but stop reading once `N` paths are collected.
+    ///
+    /// The remaining entries can still be read from the iterator.
+    pub fn collect_paths_truncated<const N: usize>(&mut self) -> Result<heapless::Vec<PathBuf, N>> {
+        let mut paths = heapless::Vec::new();
+        while !paths.is_full() {
+            let Some(entry) = self.next() else {
+                break;
+            };
+            let entry = entry?;
+            if !matches!(entry.file_name().as_str(), "." | "..") {
+                paths.push(entry.path().into()).ok();
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Iterate over the remaining entries that are files.
+    ///
+    /// The type of an entry is returned by littlefs when reading the directory, so this does not
+    /// query the metadata of the entries.  Errors are passed through.
+    pub fn files(&mut self) -> ReadDirOfType<'_, 'a, 'b, S> {
+        ReadDirOfType {
+            read_dir: self,
+            file_type: FileType::File,
+        }
+    }
+
+    /// Iterate over the remaining entries that are directories, skipping `.` and `..`.
+    ///
+    /// The type of an entry is returned by littlefs when reading the directory, so this does not
+    /// query the metadata of the entries.  Errors are passed through.
+    pub fn dirs(&mut self) -> ReadDirOfType<'_, 'a, 'b, S> {
+        ReadDirOfType {
+            read_dir: self,
+            file_type: FileType::Dir,
+        }
+    }
+
+    /// Open the file `name` in this directory read-only and pass it to `f`, see
+    /// [`Filesystem::open_file_and_then`][].
+    ///
+    /// littlefs always resolves paths from the root, so this joins `name` onto the path of the
+    /// directory and opens the result.  It is a convenience for opening the entries while
+    /// iterating over a directory.  If `name` is absolute or leaves the directory through `..`,
+    /// [`Error::INVALID`][] is returned.  If the joined path is too long,
+    /// [`Error::FILENAME_TOO_LONG`][] is returned.
+    pub fn open_file_and_then<R>(
+        &self,
+        name: &Path,
+        f: impl FnOnce(&File<'_, '_, S>) -> Result<R>,
+    ) -> Result<R> {
+        self.fs.open_file_and_then(&self.child_path(name)?, f)
+    }
+
+    /// Open the file `name` in this directory with the given options and pass it to `f`, see
+    /// [`Filesystem::open_file_with_options_and_then`][].
+    ///
+    /// `name` is resolved like in [`open_file_and_then`](ReadDir::open_file_and_then).
+    pub fn open_file_with_options_and_then<R>(
+        &self,
+        o: impl FnOnce(&mut OpenOptions) -> &OpenOptions,
+        name: &Path,
+        f: impl FnOnce(&File<'_, '_, S>) -> Result<R>,
+    ) -> Result<R> {
+        self.fs
+            .open_file_with_options_and_then(o, &self.child_path(name)?, f)
+    }
+
+    fn child_path(&self, name: &Path) -> Result<PathBuf> {
+        self.path.join_rooted(name).map_err(|err| match err {
+            path::Error::TooLarge => Error::FILENAME_TOO_LONG,
+            _ => Error::INVALID,
+        })
+    }
+}
+
+/// Iterator over the entries of a [`ReadDir`][] with a given type.
+///
+/// See [`ReadDir::files`][] and [`ReadDir::dirs`][].
+pub struct ReadDirOfType<'r, 'a, 'b, S: driver::Storage> {
+    read_dir: &'r mut ReadDir<'a, 'b, S>,
+    file_type: FileType,
+}
+
+impl<S: driver::Storage> Iterator for ReadDirOfType<'_, '_, '_, S> {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_dir.find(|entry| match entry {
+            Ok(entry) => {
+                entry.file_type() == self.file_type
+                    && !matches!(entry.file_name().as_str(), "." | "..")
+            }
+            Err(_) => true,
+        })
+    }
+}
+
 impl<'a, S: driver::Storage> ReadDir<'a, '_, S> {
     // Safety-hatch to experiment with missing parts of API
     pub unsafe fn borrow_filesystem<'b>(&'b mut self) -> &'b Filesystem<'a, S> {
@@ -992,6 +2564,8 @@ impl<S: driver::Storage> ReadDir<'_, '_, S> {
     // have an (unsafely genereated) ReadDir with that handle; on the other hand
     // as long as ReadDir is not Copy.
     pub fn close(self) -> Result<()> {
+        #[cfg(feature = "handle-tracking")]
+        self.fs.untrack_handle(*self.alloc.borrow() as usize);
         let return_code = unsafe {
             // We need to use addr_of_mut! here instead of & mut since
             // the FFI stores a copy of a pointer to the field state,
@@ -1020,6 +2594,431 @@ impl<'a, Storage: driver::Storage> Filesystem<'a, Storage> {
         res
     }
 
+    /// Number of data blocks occupied by the file at `path`.
+    ///
+    /// Small files are stored inline in the metadata pair of their directory.  Inline files
+    /// don't occupy data blocks, so `0` is returned for them, even if they are not empty.  For
+    /// other files, the blocks of the skip-list that stores the contents are counted, which
+    /// includes the space for the pointers between the blocks.  The metadata pair of the
+    /// directory is shared by all its entries and is never counted.  Multiply the result with
+    /// `Storage::BLOCK_SIZE` to get the size in bytes.
+    ///
+    /// If `path` is a directory, [`Error::PATH_IS_DIR`][] is returned.
+    pub fn blocks_used_by(&self, path: &Path) -> Result<usize> {
+        self.open_file_and_then(path, |file| {
+            let state = unsafe { &(*(*file.alloc.borrow())).state };
+            if state.flags & ll::lfs_open_flags_LFS_F_INLINE != 0 || state.ctz.size == 0 {
+                return Ok(0);
+            }
+            Ok(ctz_index(Storage::BLOCK_SIZE, state.ctz.size as usize - 1) + 1)
+        })
+    }
+
+    /// Call `f` with every data block occupied by the file at `path`.
+    ///
+    /// The contents of a file are stored in a CTZ skip-list:  the file only references the last
+    /// block, and each block starts with pointers to previous blocks.  This method follows the
+    /// first pointer of each block, so `f` is called for the last block first and for the first
+    /// block last, and every block after the first one is read from the storage to find its
+    /// predecessor.  The blocks are those of the last committed version of the file.
+    ///
+    /// Inline files don't occupy data blocks, so `f` is not called for them, see
+    /// [`Filesystem::blocks_used_by`][].  The metadata pair of the directory is not included.
+    /// If `path` is a directory, [`Error::PATH_IS_DIR`][] is returned.
+    pub fn file_blocks(&self, path: &Path, mut f: impl FnMut(ll::lfs_block_t)) -> Result<()> {
+        self.open_file_and_then(path, |file| {
+            let state = unsafe { &(*(*file.alloc.borrow())).state };
+            if state.flags & ll::lfs_open_flags_LFS_F_INLINE != 0 || state.ctz.size == 0 {
+                return Ok(());
+            }
+            let config = addr_of!(self.alloc.borrow().config);
+            let mut buf = Bytes::<Storage::CACHE_SIZE>::default();
+            // the smallest multiple of the read size that holds the pointer
+            let pointer_size = Storage::READ_SIZE * (1 + 3 / Storage::READ_SIZE);
+            let pointer = &mut buf[..pointer_size];
+
+            let mut block = state.ctz.head;
+            let mut index = ctz_index(Storage::BLOCK_SIZE, state.ctz.size as usize - 1);
+            loop {
+                f(block);
+                if index == 0 {
+                    return Ok(());
+                }
+                let return_code = Self::lfs_config_read(
+                    config,
+                    block,
+                    0,
+                    pointer.as_mut_ptr().cast(),
+                    pointer_size as _,
+                );
+                result_from((), return_code)?;
+                block = ll::lfs_block_t::from_le_bytes([
+                    pointer[0], pointer[1], pointer[2], pointer[3],
+                ]);
+                index -= 1;
+            }
+        })
+    }
+
+    /// Verify the CRCs of all metadata pairs.
+    ///
+    /// This reads the metadata pairs from the storage again, following the chain of tails like
+    /// [`lfs_fs_traverse`][ll::lfs_fs_traverse], and checks the CRCs of the commits like littlefs
+    /// does when fetching a pair:  the block with the newer revision is used if it contains a
+    /// valid commit, otherwise the other block.  In a block, the first commit that fails its CRC
+    /// ends the block, as this is what a commit interrupted by a power loss looks like, and
+    /// littlefs uses the commits before it.  It is intended to validate an image before relying
+    /// on it, for example in a bootloader.  File data is not checked.
+    ///
+    /// If neither block of a pair contains a valid commit, if a pair refers to blocks outside of
+    /// the filesystem or if the tails form a cycle, [`Error::CORRUPTION`][] is returned and the
+    /// offending pair is logged.
+    pub fn verify_metadata(&self) -> Result<()> {
+        let config = addr_of!(self.alloc.borrow().config);
+        let block_count = self.alloc.borrow().config.block_count;
+        let mut reader = MetadataReader::<Storage>::new(config);
+        let mut pair = [0, 1];
+        for _ in 0..block_count {
+            if pair.iter().any(|&block| block >= block_count) {
+                error_now!("invalid metadata pair {:?}", pair);
+                return Err(Error::CORRUPTION);
+            }
+            let revs = [
+                reader.read_u32_le(pair[0], 0)?,
+                reader.read_u32_le(pair[1], 0)?,
+            ];
+            if (revs[1].wrapping_sub(revs[0]) as i32) > 0 {
+                pair.swap(0, 1);
+            }
+            let tail = match reader.last_commit(pair[0])? {
+                Some(tail) => tail,
+                None => match reader.last_commit(pair[1])? {
+                    Some(tail) => tail,
+                    None => {
+                        error_now!("no valid commit in metadata pair {:?}", pair);
+                        return Err(Error::CORRUPTION);
+                    }
+                },
+            };
+            if tail.contains(&LFS_BLOCK_NULL) {
+                return Ok(());
+            }
+            pair = tail;
+        }
+        error_now!("metadata pairs form a cycle");
+        Err(Error::CORRUPTION)
+    }
+
+    /// Check whether `path` refers to the root directory of this filesystem.
+    ///
+    /// littlefs has no current directory and resolves relative paths from the root, and it
+    /// removes `.` and `..` components lexically.  So besides `/`, the empty path and paths like
+    /// `.`, `//` or `/a/..` also refer to the root directory.  The path is not accessed on the
+    /// storage.
+    ///
+    /// To route paths to different filesystems in a virtual filesystem, strip the mount point
+    /// with [`Path::strip_prefix`][] and pass the remainder to the filesystem mounted there:
+    /// a relative remainder refers to the same entry as the absolute path, and an empty
+    /// remainder refers to the root directory.
+    pub fn is_root_path(&self, path: &Path) -> bool {
+        let mut depth = 0usize;
+        for component in path.as_str().split('/') {
+            match component {
+                "" | "." => {}
+                ".." => depth = depth.saturating_sub(1),
+                _ => depth += 1,
+            }
+        }
+        depth == 0
+    }
+
+    /// Count the entries of a directory, not including `.` and `..`.
+    ///
+    /// littlefs does not store the number of entries, so this reads the whole directory and
+    /// takes time linear in the number of entries.
+    pub fn count_dir(&self, path: &Path) -> Result<usize> {
+        self.read_dir_and_then(path, |read_dir| {
+            let mut count = 0;
+            for entry in read_dir {
+                if !matches!(entry?.file_name().as_str(), "." | "..") {
+                    count += 1;
+                }
+            }
+            Ok(count)
+        })
+    }
+
+    /// Call `f` with the path of every file in `dir` whose name matches `pattern`.
+    ///
+    /// The pattern is matched against the file name, not the full path, and supports two
+    /// wildcards:  `*` matches any sequence of bytes, including an empty one, and `?` matches
+    /// exactly one byte.  All other bytes match themselves, case-sensitively; there is no way
+    /// to escape the wildcards.  Only the files directly in `dir` are considered, directories
+    /// are skipped and there is no recursive `**`.  For example, `log.*` matches `log.1` and
+    /// `log.` but not `log`, and `?.bin` matches `a.bin` but not `ab.bin`.
+    pub fn glob(&self, dir: &Path, pattern: &str, mut f: impl FnMut(&Path)) -> Result<()> {
+        self.read_dir_and_then(dir, |read_dir| {
+            for entry in read_dir.files() {
+                let entry = entry?;
+                if glob_match(pattern.as_bytes(), entry.file_name().as_str().as_bytes()) {
+                    f(entry.path());
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Call `f` with every entry below `path`, recursing into subdirectories.
+    ///
+    /// The entries are visited depth-first:  `f` is called with a directory before its
+    /// contents.  `.` and `..` are skipped.  The entries of a directory are visited in the order
+    /// in which littlefs returns them, and one directory handle per level is kept open.  Use
+    /// [`Filesystem::walk_dir_sorted`][] for a reproducible order.
+    pub fn walk_dir(&self, path: &Path, mut f: impl FnMut(&DirEntry)) -> Result<()> {
+        self.walk_dir_inner(path, &mut f)
+    }
+
+    fn walk_dir_inner(&self, path: &Path, f: &mut dyn FnMut(&DirEntry)) -> Result<()> {
+        self.read_dir_and_then(path, |read_dir| {
+            for entry in read_dir {
+                let entry = entry?;
+                if matches!(entry.file_name().as_str(), "." | "..") {
+                    continue;
+                }
+                f(&entry);
+                if entry.file_type().is_dir() {
+                    self.walk_dir_inner(entry.path(), f)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Call `f` with every entry below `path` like [`Filesystem::walk_dir`][], but with the
+    /// entries of each directory sorted with [`Path::cmp_lfs`][].
+    ///
+    /// This makes the traversal reproducible across devices.  The paths of the entries of each
+    /// directory are first collected, which buffers up to `N` paths per level on the stack, so
+    /// it needs `N * size_of::<PathBuf>()` (about `N * 256`) bytes of RAM per directory level.
+    /// If a directory has more than `N` entries, [`Error::NO_MEMORY`][] is returned.  As the
+    /// metadata is queried separately for every entry, this is also slower.
+    pub fn walk_dir_sorted<const N: usize>(
+        &self,
+        path: &Path,
+        mut f: impl FnMut(&DirEntry),
+    ) -> Result<()> {
+        self.walk_dir_sorted_inner::<N>(path, &mut f)
+    }
+
+    fn walk_dir_sorted_inner<const N: usize>(
+        &self,
+        path: &Path,
+        f: &mut dyn FnMut(&DirEntry),
+    ) -> Result<()> {
+        let mut paths = self.read_dir_and_then(path, |read_dir| read_dir.collect_paths::<N>())?;
+        paths.sort_unstable_by(|a, b| a.cmp_lfs(b));
+        for path in paths {
+            let metadata = self.metadata(&path)?;
+            let file_name = path.file_name().map(PathBuf::from).unwrap_or_default();
+            let entry = DirEntry::new(file_name, metadata, path);
+            f(&entry);
+            if entry.file_type().is_dir() {
+                self.walk_dir_sorted_inner::<N>(entry.path(), f)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write all files and directories below `root` to `out` in a simple stream format.
+    ///
+    /// The entries are written in the order of [`Filesystem::walk_dir`][], so every directory is
+    /// written before its contents.  Each entry is written as a record:
+    ///
+    /// | Field  | Size         | Content                                                    |
+    /// |--------|--------------|------------------------------------------------------------|
+    /// | kind   | 1 byte       | `1` for a file, `2` for a directory                        |
+    /// | length | 1 byte       | length `n` of the path                                     |
+    /// | path   | `n` bytes    | path relative to `root`, ASCII, without leading slash      |
+    /// | size   | 4 bytes      | files only: size `m` of the file, unsigned little-endian   |
+    /// | data   | `m` bytes    | files only: contents of the file                           |
+    ///
+    /// The stream ends with a single `0` byte.  Attributes are not included.  Files are read in
+    /// chunks of `Storage::CACHE_SIZE` bytes.  Use [`Filesystem::deserialize_tree`][] to restore
+    /// the tree.
+    pub fn serialize_tree(&self, root: &Path, out: &impl io::Write) -> Result<()> {
+        let mut result = Ok(());
+        self.walk_dir(root, |entry| {
+            if result.is_ok() {
+                result = self.serialize_entry(root, entry, out);
+            }
+        })?;
+        result?;
+        out.write_all(&[0])
+    }
+
+    fn serialize_entry(&self, root: &Path, entry: &DirEntry, out: &impl io::Write) -> Result<()> {
+        let path = entry.path().strip_prefix(root).ok_or(Error::INVALID)?;
+        let path = path.as_str().as_bytes();
+        let kind = if entry.file_type().is_dir() { 2 } else { 1 };
+        out.write_all(&[kind, path.len() as u8])?;
+        out.write_all(path)?;
+        if entry.file_type().is_dir() {
+            return Ok(());
+        }
+        let size = u32::try_from(entry.metadata().len()).map_err(|_| Error::FILE_TOO_BIG)?;
+        out.write_all(&size.to_le_bytes())?;
+        let mut buf = Bytes::<Storage::CACHE_SIZE>::default();
+        File::open_and_then(self, entry.path(), |file| {
+            let mut remaining = size as usize;
+            while remaining > 0 {
+                let n = cmp::min(remaining, buf.len());
+                io::Read::read_exact(file, &mut buf[..n])?;
+                out.write_all(&buf[..n])?;
+                remaining -= n;
+            }
+            Ok(())
+        })
+    }
+
+    /// Restore files and directories written by [`Filesystem::serialize_tree`][] below `root`.
+    ///
+    /// `root` must exist.  Existing directories are reused and existing files are overwritten.
+    /// If a path in the stream is absolute or escapes `root`, see [`Path::join_rooted`][],
+    /// [`Error::INVALID`][] is returned.  If the stream is malformed or ends early,
+    /// [`Error::INVALID`][] or [`Error::IO`][] is returned.  The entries restored before an error
+    /// are kept.
+    pub fn deserialize_tree(&self, root: &Path, input: &impl io::Read) -> Result<()> {
+        let mut buf = Bytes::<Storage::CACHE_SIZE>::default();
+        loop {
+            let mut header = [0; 2];
+            input.read_exact(&mut header[..1])?;
+            if header[0] == 0 {
+                return Ok(());
+            }
+            input.read_exact(&mut header[1..])?;
+            let [kind, len] = header;
+            if len as usize > PathBuf::MAX_SIZE {
+                return Err(Error::INVALID);
+            }
+            let mut path = [0; PathBuf::MAX_SIZE];
+            let path = &mut path[..len as usize];
+            input.read_exact(path)?;
+            let path = PathBuf::try_from(&*path).map_err(|_| Error::INVALID)?;
+            let path = root.join_rooted(&path).map_err(|_| Error::INVALID)?;
+            match kind {
+                1 => {
+                    let mut size = [0; 4];
+                    input.read_exact(&mut size)?;
+                    let size = u32::from_le_bytes(size) as usize;
+                    File::create_and_then(self, &path, |file| {
+                        let mut remaining = size;
+                        while remaining > 0 {
+                            let n = cmp::min(remaining, buf.len());
+                            input.read_exact(&mut buf[..n])?;
+                            io::Write::write_all(file, &buf[..n])?;
+                            remaining -= n;
+                        }
+                        Ok(())
+                    })?;
+                }
+                2 => match self.create_dir(&path) {
+                    Ok(()) | Err(Error::ENTRY_ALREADY_EXISTED) => {}
+                    Err(error) => return Err(error),
+                },
+                _ => return Err(Error::INVALID),
+            }
+        }
+    }
+
+    /// Call `f` with the path of every entry in `dir` that was modified after `since`.
+    ///
+    /// The modification time of an entry is read from the attribute [`TIMESTAMP_ATTRIBUTE_ID`][],
+    /// and only entries with a timestamp greater than `since` are passed to `f`.  Entries without
+    /// a valid timestamp attribute are handled according to `missing`.  Files and directories
+    /// are considered, but `.` and `..` are skipped and subdirectories are not searched
+    /// recursively.
+    pub fn read_dir_since(
+        &self,
+        dir: &Path,
+        since: u64,
+        missing: MissingTimestamp,
+        mut f: impl FnMut(&Path),
+    ) -> Result<()> {
+        self.read_dir_and_then(dir, |read_dir| {
+            for entry in read_dir {
+                let entry = entry?;
+                if matches!(entry.file_name().as_str(), "." | "..") {
+                    continue;
+                }
+                let mut buffer = [0; 8];
+                let timestamp = self
+                    .attribute(entry.path(), TIMESTAMP_ATTRIBUTE_ID, &mut buffer)?
+                    .filter(|attribute| attribute.total_size() == 8)
+                    .map(|attribute| {
+                        let mut bytes = [0; 8];
+                        bytes.copy_from_slice(attribute.data());
+                        u64::from_le_bytes(bytes)
+                    });
+                let include = match timestamp {
+                    Some(timestamp) => timestamp > since,
+                    None => missing == MissingTimestamp::Include,
+                };
+                if include {
+                    f(entry.path());
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Read the file names of the entries of a directory into `out`, sorted with
+    /// [`Path::cmp_lfs`][].
+    ///
+    /// The entries `.` and `..` are skipped, see [`Filesystem::read_dir_sorted_with_dots`][] to
+    /// include them.  If the directory has more entries than fit into `out`, only the first
+    /// entries in sort order are kept.  Returns the number of entries written to `out`.
+    pub fn read_dir_sorted(&self, path: &Path, out: &mut [PathBuf]) -> Result<usize> {
+        self.read_dir_sorted_inner(path, out, false)
+    }
+
+    /// Read the file names of the entries of a directory into `out` like
+    /// [`Filesystem::read_dir_sorted`][], including the entries `.` and `..`.
+    pub fn read_dir_sorted_with_dots(&self, path: &Path, out: &mut [PathBuf]) -> Result<usize> {
+        self.read_dir_sorted_inner(path, out, true)
+    }
+
+    fn read_dir_sorted_inner(
+        &self,
+        path: &Path,
+        out: &mut [PathBuf],
+        include_dots: bool,
+    ) -> Result<usize> {
+        self.read_dir_and_then(path, |read_dir| {
+            let mut count = 0;
+            for entry in read_dir {
+                let entry = entry?;
+                let name = entry.file_name();
+                if !include_dots && matches!(name.as_str(), "." | "..") {
+                    continue;
+                }
+                let sorted = &out[..count];
+                let index = sorted.partition_point(|other| other.cmp_lfs(name).is_lt());
+                if matches!(sorted.get(index), Some(other) if other.as_path() == name) {
+                    continue;
+                }
+                if index == out.len() {
+                    continue;
+                }
+                if count < out.len() {
+                    count += 1;
+                }
+                out[index..count].rotate_right(1);
+                out[index] = name.into();
+            }
+            Ok(count)
+        })
+    }
+
     /// Returns a pseudo-iterator over the entries within a directory.
     ///
     /// This is unsafe since it can induce UB just like File::open.
@@ -1041,9 +3040,13 @@ impl<'a, Storage: driver::Storage> Filesystem<'a, Storage> {
             alloc: RefCell::new(alloc),
             fs: self,
             path,
+            finished: false,
         };
 
-        result_from(read_dir, return_code)
+        let read_dir = result_from(read_dir, return_code)?;
+        #[cfg(feature = "handle-tracking")]
+        self.track_handle(*read_dir.alloc.borrow() as usize, HandleKind::Dir, path);
+        Ok(read_dir)
     }
 }
 
@@ -1054,6 +3057,34 @@ impl<'a, Storage: driver::Storage> Filesystem<'a, Storage> {
         Ok(fs)
     }
 
+    /// Mount the filesystem using the buffers provided in `buffers` instead of the buffers of
+    /// `alloc`.
+    ///
+    /// This makes it possible to place the buffers in a dedicated memory region.  Note that
+    /// `alloc` still contains its own buffers, they are just not used.  If one of the buffers is
+    /// too small, [`Error::INVALID`][] is returned, see [`MountBuffers`][] for the requirements.
+    pub fn mount_with_buffers(
+        alloc: &'a mut Allocation<Storage>,
+        storage: &'a mut Storage,
+        buffers: MountBuffers<'a>,
+    ) -> Result<Self> {
+        if buffers.read.len() < Storage::CACHE_SIZE::USIZE
+            || buffers.prog.len() < Storage::CACHE_SIZE::USIZE
+            || buffers.lookahead.len() < Storage::LOOKAHEAD_SIZE::USIZE
+        {
+            return Err(Error::INVALID);
+        }
+        let fs = Self::new(alloc, storage);
+        {
+            let mut alloc = fs.alloc.borrow_mut();
+            alloc.config.read_buffer = buffers.read.as_mut_ptr().cast();
+            alloc.config.prog_buffer = buffers.prog.as_mut_ptr().cast();
+            alloc.config.lookahead_buffer = buffers.lookahead.as_mut_ptr().cast();
+        }
+        fs.raw_mount()?;
+        Ok(fs)
+    }
+
     /// Mount the filesystem or, if that fails, call `f` with the mount error and the storage and then try again.
     pub fn mount_or_else<F>(
         alloc: &'a mut Allocation<Storage>,
@@ -1072,6 +3103,7 @@ impl<'a, Storage: driver::Storage> Filesystem<'a, Storage> {
     }
 
     fn raw_mount(&self) -> Result<()> {
+        validate_geometry(&*self.storage)?;
         let mut alloc = self.alloc.borrow_mut();
         let return_code = unsafe { ll::lfs_mount(&mut alloc.state, &alloc.config) };
         drop(alloc);
@@ -1089,9 +3121,36 @@ impl<'a, Storage: driver::Storage> Filesystem<'a, Storage> {
         Filesystem {
             alloc: RefCell::new(alloc),
             storage,
+            open_files: Cell::new(0),
+            cached_available_blocks: Cell::new(None),
+            #[cfg(feature = "handle-tracking")]
+            handles: RefCell::new(heapless::Vec::new()),
         }
     }
 
+    /// Unmount the filesystem and return the allocation and the storage.
+    ///
+    /// In contrast to [`into_inner`](Filesystem::into_inner), this calls `lfs_unmount`.  The
+    /// allocation keeps the configuration, so the filesystem can be mounted again with
+    /// [`Filesystem::mount`][], for example after accessing the storage directly.  As this takes
+    /// `self`, no files or directories borrowing the filesystem can be open.
+    pub fn unmount(self) -> Result<(&'a mut Allocation<Storage>, &'a mut Storage)> {
+        let return_code = unsafe { ll::lfs_unmount(&mut self.alloc.borrow_mut().state) };
+        result_from(self.into_inner(), return_code)
+    }
+
+    /// Unmount and mount the filesystem again, reusing its configuration and buffers.
+    ///
+    /// This also reuses buffers passed to [`Filesystem::mount_with_buffers`][], which are still
+    /// borrowed by the filesystem, so they cannot have been modified in the meantime.
+    pub fn remount(self) -> Result<Self> {
+        let return_code = unsafe { ll::lfs_unmount(&mut self.alloc.borrow_mut().state) };
+        result_from((), return_code)?;
+        self.cached_available_blocks.set(None);
+        self.raw_mount()?;
+        Ok(self)
+    }
+
     /// Deconstruct `Filesystem`, intention is to allow access to
     /// the underlying Flash peripheral in driver::Storage etc.
     ///
@@ -1168,6 +3227,43 @@ impl<'a, Storage: driver::Storage> Filesystem<'a, Storage> {
         Ok(contents)
     }
 
+    /// Stream the contents of a file through `update`, for example to compute a hash.
+    ///
+    /// The file is read in chunks of at most `Storage::CACHE_SIZE` bytes into a stack buffer,
+    /// and `update` is called with each chunk in order.  Returns the number of bytes read.  Any
+    /// hash or checksum that can be updated incrementally can be used, for example a
+    /// [`Hasher`](core::hash::Hasher) with `|chunk| hasher.write(chunk)`.
+    pub fn hash_file(&self, path: &Path, mut update: impl FnMut(&[u8])) -> Result<usize> {
+        let mut buf = Bytes::<Storage::CACHE_SIZE>::default();
+        File::open_and_then(self, path, |file| {
+            let mut total = 0;
+            loop {
+                let n = file.read(&mut buf)?;
+                if n == 0 {
+                    return Ok(total);
+                }
+                update(&buf[..n]);
+                total += n;
+            }
+        })
+    }
+
+    /// Read the last bytes of a file into `buf`.
+    ///
+    /// If the file is longer than `buf`, the last `buf.len()` bytes are read.  Otherwise, the
+    /// whole file is read into the start of `buf`.  Only the data that is returned is read from
+    /// the storage.  Returns the number of bytes read.
+    pub fn read_tail(&self, path: &Path, buf: &mut [u8]) -> Result<usize> {
+        File::open_and_then(self, path, |file| {
+            use io::Read;
+            let len = file.len()?;
+            let n = cmp::min(len, buf.len());
+            file.seek(io::SeekFrom::Start((len - n) as u32))?;
+            file.read_exact(&mut buf[..n])?;
+            Ok(n)
+        })
+    }
+
     /// Read a chunk of a file into a bytes vector
     /// Returns the data and the size of the file
     pub fn read_chunk<const N: usize>(
@@ -1200,6 +3296,97 @@ impl<'a, Storage: driver::Storage> Filesystem<'a, Storage> {
         Ok(())
     }
 
+    /// Atomically replace the contents of a file.
+    ///
+    /// The data is written to the temporary file `<path>.tmp` in the same directory, which is
+    /// then renamed to `path`.  As littlefs renames files atomically, `path` either contains its
+    /// previous contents or `contents` after a power loss, but never a partially written file.
+    /// If writing or renaming fails, the temporary file is removed.  A temporary file that is
+    /// left behind by a power loss is overwritten by the next call.
+    ///
+    /// If `path` is empty or ends with a slash, [`Error::INVALID`][] is returned.  If the name of
+    /// the temporary file would be too long, [`Error::FILENAME_TOO_LONG`][] is returned.
+    pub fn write_atomic(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let tmp = with_suffix(path, b".tmp")?;
+
+        let result = self
+            .write(&tmp, contents)
+            .and_then(|_| self.rename(&tmp, path));
+        if result.is_err() {
+            self.remove(&tmp).ok();
+        }
+        result
+    }
+
+    /// Create a new file with the given contents.
+    ///
+    /// Unlike [`Filesystem::write`][], this fails with [`Error::ENTRY_ALREADY_EXISTED`][] if
+    /// `path` already exists, in which case the existing file is not modified.  If writing or
+    /// syncing the contents fails, the new file is removed again, so that no empty or partially
+    /// written file remains.  Note that littlefs creates the file when it is opened, so after a
+    /// power loss during the write, an empty or partially written file may be left behind.
+    pub fn create_with(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let mut created = false;
+        let result = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open_and_then(self, path, |file| {
+                use io::Write;
+                created = true;
+                file.write_all(contents)?;
+                file.sync()
+            });
+        if result.is_err() && created {
+            self.remove(path).ok();
+        }
+        result
+    }
+
+    /// Create a link at `link` that points to `target`.
+    ///
+    /// littlefs does not support symbolic links, so links are emulated:  a link is a regular
+    /// file that contains the target path as ASCII bytes without a trailing null, and that has
+    /// the attribute [`LINK_ATTRIBUTE_ID`][] set to
+    /// [`LINK_MARKER`][].  Both are written when the file is closed, so
+    /// the file is either an empty file without the marker or a complete link after a power
+    /// loss.
+    ///
+    /// Links are not transparent:  all other methods treat a link as a regular file.  Use
+    /// [`Filesystem::read_link`][] to resolve it.  The target does not need to exist.  If `link`
+    /// already exists, [`Error::ENTRY_ALREADY_EXISTED`][] is returned.
+    pub fn create_link(&self, link: &Path, target: &Path) -> Result<()> {
+        let mut cache = Bytes::<Storage::CACHE_SIZE>::default();
+        let mut marker = [0; LINK_MARKER.len()];
+        marker.copy_from_slice(LINK_MARKER);
+        let mut attrs = [FileAttr::new(LINK_ATTRIBUTE_ID, &mut marker)];
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open_cfg_and_then(
+                self,
+                link,
+                FileConfig::new(&mut cache, &mut attrs),
+                |file| {
+                    use io::Write;
+                    file.write_all(target.as_str().as_bytes())
+                },
+            )
+    }
+
+    /// Return the target of a link created with [`Filesystem::create_link`][].
+    ///
+    /// If `link` is not a file with the link marker, or if it does not contain a valid path,
+    /// [`Error::INVALID`][] is returned.
+    pub fn read_link(&self, link: &Path) -> Result<PathBuf> {
+        let mut marker = [0; LINK_MARKER.len()];
+        match self.attribute(link, LINK_ATTRIBUTE_ID, &mut marker)? {
+            Some(attribute) if !attribute.is_truncated() && attribute.data() == LINK_MARKER => {}
+            _ => return Err(Error::INVALID),
+        }
+        let target: heapless::Vec<u8, { PathBuf::MAX_SIZE_PLUS_ONE }> = self.read(link)?;
+        PathBuf::try_from(&target[..]).map_err(|_| Error::INVALID)
+    }
+
     /// Write a slice as a chunk of a file.
     ///
     /// This function will not create a file if it does not exist,
@@ -1249,6 +3436,24 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn glob_match() {
+        let matches =
+            |pattern: &str, name: &str| super::glob_match(pattern.as_bytes(), name.as_bytes());
+        assert!(matches("log.*", "log.1"));
+        assert!(matches("log.*", "log."));
+        assert!(!matches("log.*", "log"));
+        assert!(matches("?.bin", "a.bin"));
+        assert!(!matches("?.bin", "ab.bin"));
+        assert!(matches("*", ""));
+        assert!(matches("**", "abc"));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(!matches("a*b*c", "aXbYbZ"));
+        assert!(matches("*.tar.gz", "x.tar.tar.gz"));
+        assert!(!matches("", "a"));
+        assert!(!matches("A", "a"));
+    }
+
     #[test]
     fn todo() {
         let mut test_storage = TestStorage::new();
@@ -1,6 +1,8 @@
 #![cfg_attr(not(test), no_std)]
 // FIXME
 #![allow(clippy::missing_safety_doc)]
+// usize::is_multiple_of requires Rust 1.87
+#![allow(clippy::manual_is_multiple_of)]
 
 /*!
 
@@ -133,7 +135,10 @@ pub use littlefs2_core::path;
 
 /// Traits and types for core I/O functionality.
 pub mod io {
-    pub use littlefs2_core::{Error, OpenSeekFrom, Read, Result, Seek, SeekFrom, Write};
+    pub use littlefs2_core::{
+        BufReader, BufWriter, Bytes, Error, FromBytes, OpenSeekFrom, Read, Result, Seek, SeekFrom,
+        Write,
+    };
 
     pub mod prelude {
         //! Export of the Read, Write and Seek traits for ease of use.
@@ -144,7 +149,9 @@ pub mod io {
 
 /// Paths
 pub mod path {
-    pub use littlefs2_core::{Ancestors, Iter, Path, PathBuf, PathError as Error};
+    pub use littlefs2_core::{
+        Ancestors, Escaped, Iter, IterWithLast, Path, PathBuf, PathError as Error, StrOrderedPath,
+    };
     pub type Result<T> = core::result::Result<T, Error>;
 }
 
@@ -163,6 +170,8 @@ pub mod consts;
 pub mod driver;
 pub mod fs;
 pub mod object_safe;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 /// The version of the C backend.
 pub const BACKEND_VERSION: Version = Version(ll::LFS_VERSION);
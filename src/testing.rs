@@ -0,0 +1,95 @@
+//! Helpers for testing code that uses littlefs.
+//!
+//! This module is only available with the `testing` feature.
+
+use core::slice;
+
+use generic_array::typenum::consts;
+
+use crate::{driver::Storage, io::Result};
+
+/// An in-memory [`Storage`][] implementation with a configurable geometry.
+///
+/// The storage consists of `BLOCK_COUNT` blocks of `BLOCK_SIZE` bytes that are stored inline,
+/// so large geometries should be allocated on the heap, for example using `Box`.  Reads and
+/// writes have a granularity of one byte, and the cache size is 32 bytes, so `BLOCK_SIZE` must
+/// be a multiple of 32 and at least 128 bytes.  Erased bytes have the value `0xff`.
+///
+/// ```
+/// use littlefs2::{fs::Filesystem, path, testing::RamStorage};
+///
+/// let mut storage = RamStorage::<256, 64>::new();
+/// Filesystem::format(&mut storage).unwrap();
+/// Filesystem::mount_and_then(&mut storage, |fs| fs.write(path!("test"), b"data")).unwrap();
+/// ```
+pub struct RamStorage<const BLOCK_SIZE: usize, const BLOCK_COUNT: usize> {
+    blocks: [[u8; BLOCK_SIZE]; BLOCK_COUNT],
+}
+
+impl<const BLOCK_SIZE: usize, const BLOCK_COUNT: usize> RamStorage<BLOCK_SIZE, BLOCK_COUNT> {
+    pub const ERASE_VALUE: u8 = 0xff;
+
+    const VALID_GEOMETRY: () = assert!(
+        BLOCK_SIZE >= 128 && BLOCK_SIZE % 32 == 0 && BLOCK_COUNT >= 2,
+        "invalid RamStorage geometry"
+    );
+
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::VALID_GEOMETRY;
+        Self {
+            blocks: [[Self::ERASE_VALUE; BLOCK_SIZE]; BLOCK_COUNT],
+        }
+    }
+
+    /// Returns the raw contents of the storage.
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: the blocks are stored contiguously without padding
+        unsafe { slice::from_raw_parts(self.blocks.as_ptr().cast(), BLOCK_SIZE * BLOCK_COUNT) }
+    }
+
+    /// Returns the raw contents of the storage for modification, for example to simulate
+    /// corruption.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the blocks are stored contiguously without padding
+        unsafe {
+            slice::from_raw_parts_mut(self.blocks.as_mut_ptr().cast(), BLOCK_SIZE * BLOCK_COUNT)
+        }
+    }
+}
+
+impl<const BLOCK_SIZE: usize, const BLOCK_COUNT: usize> Default
+    for RamStorage<BLOCK_SIZE, BLOCK_COUNT>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const BLOCK_SIZE: usize, const BLOCK_COUNT: usize> Storage
+    for RamStorage<BLOCK_SIZE, BLOCK_COUNT>
+{
+    const READ_SIZE: usize = 1;
+    const WRITE_SIZE: usize = 1;
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+    const BLOCK_COUNT: usize = BLOCK_COUNT;
+    type CACHE_SIZE = consts::U32;
+    type LOOKAHEAD_SIZE = consts::U1;
+
+    fn read(&mut self, off: usize, buf: &mut [u8]) -> Result<usize> {
+        buf.copy_from_slice(&self.as_bytes()[off..][..buf.len()]);
+        Ok(buf.len())
+    }
+
+    fn write(&mut self, off: usize, data: &[u8]) -> Result<usize> {
+        self.as_bytes_mut()[off..][..data.len()].copy_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn erase(&mut self, off: usize, len: usize) -> Result<usize> {
+        debug_assert!(off % Self::ERASE_SIZE == 0);
+        debug_assert!(len % Self::ERASE_SIZE == 0);
+        self.as_bytes_mut()[off..][..len].fill(Self::ERASE_VALUE);
+        Ok(len)
+    }
+}
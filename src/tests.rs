@@ -1,10 +1,12 @@
-use core::convert::TryInto;
+use core::{cell::Cell, convert::TryInto};
 use generic_array::typenum::consts;
 
 use crate::{
-    fs::{Attribute, File, Filesystem},
-    io::{Error, OpenSeekFrom, Read, Result, SeekFrom},
-    path, BACKEND_VERSION, DISK_VERSION,
+    fs::{Attribute, DirEntry, File, FileAttr, FileConfig, Filesystem, MountBuffers, OpenOptions},
+    io::{BufReader, BufWriter, Error, OpenSeekFrom, Read, Result, Seek, SeekFrom, Write},
+    path,
+    path::PathBuf,
+    BACKEND_VERSION, DISK_VERSION,
 };
 
 ram_storage!(
@@ -443,6 +445,26 @@ fn attributes() {
         let attribute = fs.attribute(filename, 37, &mut small_buffer)?.unwrap();
         assert_eq!(&data[..5], attribute.data());
         assert_eq!(data.len(), attribute.total_size());
+        assert!(attribute.is_truncated());
+
+        // a buffer of exactly the attribute size is not truncated
+        let mut exact_buffer = [0; 10];
+        let attribute = fs.attribute(filename, 37, &mut exact_buffer)?.unwrap();
+        assert_eq!(data, attribute.data());
+        assert!(!attribute.is_truncated());
+
+        // a truncated attribute can be re-read with a large enough buffer
+        let large_data = &[0xab; 300];
+        fs.set_attribute(filename, 38, large_data)?;
+        let attribute = fs.attribute(filename, 38, &mut small_buffer)?.unwrap();
+        assert!(attribute.is_truncated());
+        let total_size = attribute.total_size();
+        assert_eq!(total_size, large_data.len());
+        let attribute = fs
+            .attribute(filename, 38, &mut buffer[..total_size])?
+            .unwrap();
+        assert!(!attribute.is_truncated());
+        assert_eq!(large_data, attribute.data());
 
         // if the input data is too long, an error is returned
         let long_data = &[0xff; 1024];
@@ -532,3 +554,2365 @@ fn test_iter_dirs() {
 //     t.compile_fail("tests/ui/*-fail.rs");
 //     t.pass("tests/ui/*-pass.rs");
 // }
+
+#[test]
+fn test_subview() {
+    let mut backend = OtherRam::default();
+    let mut storage = OtherRamStorage::new(&mut backend);
+
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("records.bin"), b"firstsecondthird")?;
+        fs.open_file_with_options_and_then(
+            |options| options.read(true).write(true),
+            path!("records.bin"),
+            |file| {
+                let record = file.subview(5, 6);
+                assert_eq!(record.len(), 6);
+
+                let mut buf = [0u8; 16];
+                assert_eq!(record.read(&mut buf)?, 6);
+                assert_eq!(&buf[..6], b"second");
+                // reads past the region return EOF
+                assert_eq!(record.read(&mut buf)?, 0);
+
+                assert_eq!(record.seek(SeekFrom::Start(0))?, 0);
+                record.write_all(b"SECOND")?;
+                // writes past the region fail without writing
+                assert_eq!(record.write(b"!"), Err(Error::FILE_TOO_BIG));
+                assert_eq!(record.seek(SeekFrom::End(-3))?, 3);
+                assert_eq!(record.write(b"OVERFLOW"), Err(Error::FILE_TOO_BIG));
+                assert_eq!(record.seek(SeekFrom::Current(4)), Err(Error::INVALID));
+                Ok(())
+            },
+        )?;
+        let contents: heapless::Vec<u8, 16> = fs.read(path!("records.bin"))?;
+        assert_eq!(&contents, b"firstSECONDthird");
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_format_erased() {
+    let mut backend = OtherRam::default();
+    let secret = [0x42; 1024];
+
+    {
+        let mut storage = OtherRamStorage::new(&mut backend);
+        Filesystem::format(&mut storage).unwrap();
+        Filesystem::mount_and_then(&mut storage, |fs| fs.write(path!("secret.bin"), &secret))
+            .unwrap();
+    }
+    assert!(backend.buf.windows(64).any(|w| w == &secret[..64]));
+
+    {
+        let mut storage = OtherRamStorage::new(&mut backend);
+        Filesystem::format(&mut storage).unwrap();
+    }
+    // a plain format leaves the old data blocks in place
+    assert!(backend.buf.windows(64).any(|w| w == &secret[..64]));
+
+    {
+        let mut storage = OtherRamStorage::new(&mut backend);
+        Filesystem::format_erased(&mut storage).unwrap();
+        assert!(Filesystem::is_mountable(&mut storage));
+    }
+    assert!(!backend.buf.windows(64).any(|w| w == &secret[..64]));
+}
+
+#[test]
+fn test_deepest_existing() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir_all(path!("/a/b"))?;
+        assert_eq!(&*fs.deepest_existing(path!("/a/b/c/d"))?, path!("/a/b"));
+        assert_eq!(&*fs.deepest_existing(path!("/a/b"))?, path!("/a/b"));
+        assert_eq!(&*fs.deepest_existing(path!("/x/y"))?, path!("/"));
+        assert_eq!(&*fs.deepest_existing(path!("x/y"))?, path!("/"));
+        assert_eq!(&*fs.deepest_existing(path!("a/z"))?, path!("a"));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_buf_reader() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("config.txt"), b"key=value\nother=1\n")?;
+        fs.open_file_and_then(path!("config.txt"), |file| {
+            let reader = BufReader::<_, 8>::new(file);
+            let mut line = [0; 32];
+            let n = reader.read_line(&mut line)?;
+            assert_eq!(&line[..n], b"key=value\n");
+            let n = reader.read_line(&mut line)?;
+            assert_eq!(&line[..n], b"other=1\n");
+            assert_eq!(reader.read_line(&mut line)?, 0);
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_buf_writer() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_file_and_then(path!("log.txt"), |file| {
+            let writer = BufWriter::<_, 16>::new(file);
+            for i in 0..10u8 {
+                writer.write_all(&[b'0' + i, b'\n'])?;
+            }
+            writer.flush()?;
+            assert_eq!(file.len()?, 20);
+            writer.write_all(b"end")?;
+            writer.into_inner()?;
+            assert_eq!(file.len()?, 23);
+            Ok(())
+        })?;
+        let contents: heapless::Vec<u8, 32> = fs.read(path!("log.txt"))?;
+        assert_eq!(&contents[..20], b"0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n");
+        assert_eq!(&contents[20..], b"end");
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_metadata_not_found() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("dir"))?;
+        fs.write(path!("dir/file"), b"data")?;
+
+        assert_eq!(fs.metadata(path!("missing")), Err(Error::NO_SUCH_ENTRY));
+        assert_eq!(fs.metadata(path!("dir/missing")), Err(Error::NO_SUCH_ENTRY));
+        assert_eq!(
+            fs.metadata(path!("missing/file")),
+            Err(Error::NO_SUCH_ENTRY)
+        );
+
+        assert!(fs.try_exists(path!("dir/file"))?);
+        assert!(fs.try_exists(path!("dir"))?);
+        assert!(!fs.try_exists(path!("dir/missing"))?);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_open_cfg() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let mut cache = [0; 700];
+        let mut version = *b"v1";
+        let mut attrs = [FileAttr::new(1, &mut version)];
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open_cfg_and_then(
+                fs,
+                path!("file"),
+                FileConfig::new(&mut cache, &mut attrs),
+                |file| file.write_all(b"data"),
+            )?;
+
+        let mut buffer = [0; 8];
+        let attribute = fs.attribute(path!("file"), 1, &mut buffer)?.unwrap();
+        assert_eq!(attribute.data(), b"v1");
+
+        let mut version = [0; 2];
+        let mut missing = [0xff; 2];
+        let mut attrs = [
+            FileAttr::new(1, &mut version),
+            FileAttr::new(2, &mut missing),
+        ];
+        OpenOptions::new().read(true).open_cfg_and_then(
+            fs,
+            path!("file"),
+            FileConfig::new(&mut cache, &mut attrs),
+            |file| {
+                let mut data = [0; 4];
+                assert_eq!(file.read(&mut data)?, 4);
+                assert_eq!(&data, b"data");
+                Ok(())
+            },
+        )?;
+        assert_eq!(&version, b"v1");
+        assert_eq!(missing, [0xff; 2]);
+
+        let mut small_cache = [0; 1];
+        assert_eq!(
+            OpenOptions::new().read(true).open_cfg_and_then(
+                fs,
+                path!("file"),
+                FileConfig::new(&mut small_cache, &mut []),
+                |_| Ok(()),
+            ),
+            Err(Error::INVALID)
+        );
+
+        let mut large = [0; 1023];
+        assert_eq!(
+            OpenOptions::new().read(true).open_cfg_and_then(
+                fs,
+                path!("file"),
+                FileConfig::new(&mut cache, &mut [FileAttr::new(3, &mut large)]),
+                |_| Ok(()),
+            ),
+            Err(Error::INVALID)
+        );
+
+        // reusing an allocation with open does not write the attributes of open_cfg
+        let mut alloc = File::allocate();
+        let mut version = *b"v2";
+        let mut attrs = [FileAttr::new(3, &mut version)];
+        unsafe {
+            let file = OpenOptions::new().write(true).open_cfg(
+                fs,
+                &mut alloc,
+                path!("file"),
+                FileConfig::new(&mut cache, &mut attrs),
+            )?;
+            file.close()?;
+        }
+        version = *b"v3";
+        unsafe {
+            let file = OpenOptions::new()
+                .write(true)
+                .open(fs, &mut alloc, path!("file"))?;
+            file.write_all(b"more")?;
+            file.close()?;
+        }
+        let attribute = fs.attribute(path!("file"), 3, &mut buffer)?.unwrap();
+        assert_eq!(attribute.data(), b"v2");
+        assert_eq!(version, *b"v3");
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_merge_dir() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir_all(path!("/staging/sub"))?;
+        fs.write(path!("/staging/new"), b"new")?;
+        fs.write(path!("/staging/both"), b"staged")?;
+        fs.write(path!("/staging/sub/a"), b"a")?;
+        fs.create_dir_all(path!("/target/sub"))?;
+        fs.write(path!("/target/both"), b"old")?;
+        fs.write(path!("/target/sub/b"), b"b")?;
+
+        assert_eq!(
+            fs.merge_dir(path!("/staging"), path!("/target"), false),
+            Err(Error::ENTRY_ALREADY_EXISTED)
+        );
+        assert!(fs.exists(path!("/staging/both")));
+
+        fs.merge_dir(path!("/staging"), path!("/target"), true)?;
+        assert!(!fs.exists(path!("/staging")));
+        let both: heapless::Vec<u8, 16> = fs.read(path!("/target/both"))?;
+        assert_eq!(&both, b"staged");
+        assert!(fs.exists(path!("/target/new")));
+        assert!(fs.exists(path!("/target/sub/a")));
+        assert!(fs.exists(path!("/target/sub/b")));
+
+        // missing target
+        fs.merge_dir(path!("/target"), path!("/moved"), false)?;
+        assert!(!fs.exists(path!("/target")));
+        assert!(fs.exists(path!("/moved/sub/a")));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_open_file_count() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        assert_eq!(fs.open_file_count(), 0);
+        fs.create_file_and_then(path!("a"), |_| {
+            assert_eq!(fs.open_file_count(), 1);
+            fs.create_file_and_then(path!("b"), |_| {
+                assert_eq!(fs.open_file_count(), 2);
+                Ok(())
+            })
+        })?;
+        assert_eq!(fs.open_file_count(), 0);
+
+        // failed opens are not counted
+        assert_eq!(
+            fs.open_file_and_then(path!("missing"), |_| Ok(())),
+            Err(Error::NO_SUCH_ENTRY)
+        );
+        assert_eq!(fs.open_file_count(), 0);
+
+        let mut alloc = File::allocate();
+        let file = unsafe { File::open(fs, &mut alloc, path!("a"))? };
+        assert_eq!(fs.open_file_count(), 1);
+        unsafe { file.close()? };
+        assert_eq!(fs.open_file_count(), 0);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_chunks() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let data: heapless::Vec<u8, 1000> = (0..1000).map(|i| i as u8).collect();
+        fs.write(path!("file"), &data)?;
+        fs.open_file_and_then(path!("file"), |file| {
+            let mut chunks = file.chunks::<256>();
+            let mut offset = 0;
+            while let Some(chunk) = chunks.next() {
+                let chunk = chunk?;
+                assert_eq!(chunk, &data[offset..][..chunk.len()]);
+                offset += chunk.len();
+                assert!(chunk.len() == 256 || offset == data.len());
+            }
+            assert_eq!(offset, data.len());
+            assert!(chunks.next().is_none());
+
+            // exact multiple of the chunk size
+            file.seek(SeekFrom::Start(500))?;
+            let mut chunks = file.chunks::<250>();
+            assert_eq!(chunks.next().unwrap()?, &data[500..750]);
+            assert_eq!(chunks.next().unwrap()?, &data[750..]);
+            assert!(chunks.next().is_none());
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_read_dir_filter() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir_all(path!("/dir/sub1"))?;
+        fs.create_dir(path!("/dir/sub2"))?;
+        fs.write(path!("/dir/file1"), b"1")?;
+        fs.write(path!("/dir/file2"), b"2")?;
+
+        fs.read_dir_and_then(path!("/dir"), |read_dir| {
+            let mut files = read_dir.files();
+            assert_eq!(files.next().unwrap()?.file_name(), path!("file1"));
+            assert_eq!(files.next().unwrap()?.file_name(), path!("file2"));
+            assert!(files.next().is_none());
+            Ok(())
+        })?;
+        fs.read_dir_and_then(path!("/dir"), |read_dir| {
+            let mut dirs = read_dir.dirs();
+            assert_eq!(dirs.next().unwrap()?.path(), path!("/dir/sub1"));
+            assert_eq!(dirs.next().unwrap()?.path(), path!("/dir/sub2"));
+            assert!(dirs.next().is_none());
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_write_read_only() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("file"), b"data")?;
+        fs.open_file_and_then(path!("file"), |file| {
+            assert_eq!(file.write(b"more"), Err(Error::READ_ONLY));
+            assert_eq!(file.set_len(0), Err(Error::READ_ONLY));
+            Ok(())
+        })?;
+        let contents: heapless::Vec<u8, 8> = fs.read(path!("file"))?;
+        assert_eq!(&contents, b"data");
+
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open_and_then(fs, path!("file"), |file| {
+                file.write_all(b"new!")?;
+                Ok(())
+            })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_rename_with_parents() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("/file"), b"data")?;
+        assert_eq!(
+            fs.rename(path!("/file"), path!("/a/b/file")),
+            Err(Error::NO_SUCH_ENTRY)
+        );
+        fs.rename_with_parents(path!("/file"), path!("/a/b/file"))?;
+        assert!(!fs.exists(path!("/file")));
+        assert!(fs.exists(path!("/a/b/file")));
+
+        // existing parents
+        fs.rename_with_parents(path!("/a/b/file"), path!("/a/file"))?;
+        assert!(fs.exists(path!("/a/file")));
+
+        // the created directories are kept if the rename fails
+        assert_eq!(
+            fs.rename_with_parents(path!("/missing"), path!("/c/d/file")),
+            Err(Error::NO_SUCH_ENTRY)
+        );
+        assert!(fs.metadata(path!("/c/d"))?.is_dir());
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_write_atomic() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("/config"))?;
+        fs.write_atomic(path!("/config/main"), b"first")?;
+        fs.write_atomic(path!("/config/main"), b"second")?;
+        let contents: heapless::Vec<u8, 16> = fs.read(path!("/config/main"))?;
+        assert_eq!(&contents, b"second");
+        assert!(!fs.exists(path!("/config/main.tmp")));
+
+        // leftover temporary file from an interrupted write
+        fs.write(path!("/config/main.tmp"), b"partial")?;
+        fs.write_atomic(path!("/config/main"), b"third")?;
+        let contents: heapless::Vec<u8, 16> = fs.read(path!("/config/main"))?;
+        assert_eq!(&contents, b"third");
+        assert!(!fs.exists(path!("/config/main.tmp")));
+
+        // the target is a directory, so the rename fails
+        assert_eq!(
+            fs.write_atomic(path!("/config"), b"data"),
+            Err(Error::PATH_IS_DIR)
+        );
+        assert!(!fs.exists(path!("/config.tmp")));
+
+        assert_eq!(
+            fs.write_atomic(path!("/config/"), b"data"),
+            Err(Error::INVALID)
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_const_generic_ram_storage() {
+    use crate::testing::RamStorage;
+
+    fn roundtrip<const BLOCK_SIZE: usize, const BLOCK_COUNT: usize>() {
+        let mut storage = RamStorage::<BLOCK_SIZE, BLOCK_COUNT>::new();
+        Filesystem::format(&mut storage).unwrap();
+        Filesystem::mount_and_then(&mut storage, |fs| {
+            assert_eq!(fs.total_blocks(), BLOCK_COUNT);
+            assert_eq!(fs.total_space(), BLOCK_SIZE * BLOCK_COUNT);
+            fs.create_dir(path!("dir"))?;
+            fs.write(path!("dir/file"), b"data")?;
+            Ok(())
+        })
+        .unwrap();
+        Filesystem::mount_and_then(&mut storage, |fs| {
+            let contents: heapless::Vec<u8, 8> = fs.read(path!("dir/file"))?;
+            assert_eq!(&contents, b"data");
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    roundtrip::<128, 16>();
+    roundtrip::<512, 32>();
+    roundtrip::<4096, 8>();
+}
+
+#[test]
+fn remove_attribute_if_exists() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let mut buffer = [0; Attribute::MAX_SIZE as _];
+        let path = path!("file");
+        fs.write(path, b"data")?;
+        fs.set_attribute(path, 1, b"attribute")?;
+
+        assert!(fs.remove_attribute_if_exists(path, 1)?);
+        assert!(fs.attribute(path, 1, &mut buffer)?.is_none());
+        assert!(!fs.remove_attribute_if_exists(path, 1)?);
+        assert!(!fs.remove_attribute_if_exists(path, 2)?);
+
+        assert_eq!(
+            fs.remove_attribute_if_exists(path!("missing"), 1),
+            Err(Error::NO_SUCH_ENTRY)
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_fs_attribute() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let mut buffer = [0; Attribute::MAX_SIZE as usize];
+        assert!(fs.fs_attribute(1, &mut buffer)?.is_none());
+
+        fs.set_fs_attribute(1, b"schema v2")?;
+        fs.set_fs_attribute(2, &[0xaa; 100])?;
+        assert_eq!(
+            fs.set_fs_attribute(3, &[0; Attribute::MAX_SIZE as usize + 1]),
+            Err(Error::NO_SPACE)
+        );
+        Ok(())
+    })
+    .unwrap();
+
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let mut buffer = [0; Attribute::MAX_SIZE as usize];
+        let attribute = fs.fs_attribute(1, &mut buffer)?.unwrap();
+        assert_eq!(attribute.data(), b"schema v2");
+        let attribute = fs.fs_attribute(2, &mut buffer)?.unwrap();
+        assert_eq!(attribute.data(), &[0xaa; 100]);
+
+        fs.remove_fs_attribute(1)?;
+        assert!(fs.fs_attribute(1, &mut buffer)?.is_none());
+        assert!(fs.fs_attribute(2, &mut buffer)?.is_some());
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_truncate_without_create() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let options = OpenOptions::new().write(true).truncate(true).clone();
+        assert_eq!(
+            options.open_and_then(fs, path!("missing"), |_| Ok(())),
+            Err(Error::NO_SUCH_ENTRY)
+        );
+        assert!(!fs.exists(path!("missing")));
+
+        fs.write(path!("file"), b"data")?;
+        options.open_and_then(fs, path!("file"), |file| {
+            assert_eq!(file.len()?, 0);
+            Ok(())
+        })?;
+        assert_eq!(fs.metadata(path!("file"))?.len(), 0);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_available_blocks_cached() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let initial = fs.available_blocks()?;
+        assert_eq!(fs.available_blocks_cached()?, initial);
+
+        fs.write(path!("file"), &[0; 2000])?;
+        let after_write = fs.available_blocks()?;
+        assert!(after_write < initial);
+        assert_eq!(fs.available_blocks_cached()?, initial);
+
+        fs.invalidate_available_blocks();
+        assert_eq!(fs.available_blocks_cached()?, after_write);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_copy_to() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let data: heapless::Vec<u8, 2000> = (0..2000).map(|i| (i % 251) as u8).collect();
+        fs.write(path!("src"), &data)?;
+        fs.open_file_and_then(path!("src"), |src| {
+            fs.create_file_and_then(path!("all"), |dst| {
+                assert_eq!(src.copy_to(dst, None)?, 2000);
+                Ok(())
+            })?;
+            src.seek(SeekFrom::Start(100))?;
+            fs.create_file_and_then(path!("part"), |dst| {
+                assert_eq!(src.copy_to(dst, Some(1000))?, 1000);
+                assert_eq!(src.seek(SeekFrom::Current(0))?, 1100);
+                Ok(())
+            })?;
+            src.seek(SeekFrom::End(-10))?;
+            fs.create_file_and_then(path!("end"), |dst| {
+                assert_eq!(src.copy_to(dst, Some(1000))?, 10);
+                Ok(())
+            })
+        })?;
+        let all: heapless::Vec<u8, 2000> = fs.read(path!("all"))?;
+        assert_eq!(all, data);
+        let part: heapless::Vec<u8, 2000> = fs.read(path!("part"))?;
+        assert_eq!(&part, &data[100..1100]);
+        let end: heapless::Vec<u8, 2000> = fs.read(path!("end"))?;
+        assert_eq!(&end, &data[1990..]);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_bytes() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("file"), b"key=value;other=1;")?;
+        fs.open_file_and_then(path!("file"), |file| {
+            let position = file.bytes().position(|byte| byte == Ok(b';'));
+            assert_eq!(position, Some(9));
+            let bytes: Result<heapless::Vec<u8, 32>> = file.bytes().collect();
+            assert!(bytes?.is_empty());
+            file.seek(SeekFrom::Start(10))?;
+            let bytes: Result<heapless::Vec<u8, 32>> = file.bytes().collect();
+            assert_eq!(&bytes?, b"other=1;");
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+struct FaultyStorage<'a> {
+    inner: crate::testing::RamStorage<256, 64>,
+    fail_reads: &'a Cell<bool>,
+}
+
+impl crate::driver::Storage for FaultyStorage<'_> {
+    const READ_SIZE: usize = 1;
+    const WRITE_SIZE: usize = 1;
+    const BLOCK_SIZE: usize = 256;
+    const BLOCK_COUNT: usize = 64;
+    type CACHE_SIZE = consts::U32;
+    type LOOKAHEAD_SIZE = consts::U1;
+
+    fn read(&mut self, off: usize, buf: &mut [u8]) -> Result<usize> {
+        if self.fail_reads.get() {
+            return Err(Error::CORRUPTION);
+        }
+        self.inner.read(off, buf)
+    }
+
+    fn write(&mut self, off: usize, data: &[u8]) -> Result<usize> {
+        self.inner.write(off, data)
+    }
+
+    fn erase(&mut self, off: usize, len: usize) -> Result<usize> {
+        self.inner.erase(off, len)
+    }
+}
+
+#[test]
+fn test_close_error() {
+    let fail_reads = Cell::new(false);
+    let mut storage = FaultyStorage {
+        inner: Default::default(),
+        fail_reads: &fail_reads,
+    };
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        // the write is cached, and committing it on close fails
+        let result = fs.create_file_and_then(path!("config"), |file| {
+            file.write_all(b"critical")?;
+            fail_reads.set(true);
+            Ok(())
+        });
+        assert_eq!(result, Err(Error::CORRUPTION));
+        assert_eq!(fs.open_file_count(), 0);
+        fail_reads.set(false);
+
+        // sync surfaces the error inside the closure
+        let result = fs.create_file_and_then(path!("config"), |file| {
+            file.write_all(b"critical")?;
+            fail_reads.set(true);
+            let result = file.sync();
+            fail_reads.set(false);
+            result
+        });
+        assert_eq!(result, Err(Error::CORRUPTION));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_read_dir_corruption() {
+    let fail_reads = Cell::new(false);
+    let mut storage = FaultyStorage {
+        inner: Default::default(),
+        fail_reads: &fail_reads,
+    };
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("dir"))?;
+        for i in 0..32 {
+            let path = PathBuf::try_from(format!("dir/file{i:02}").as_str()).unwrap();
+            fs.create_file_and_then(&path, |_| Ok(()))?;
+        }
+        fs.read_dir_and_then(path!("dir"), |read_dir| {
+            assert!(read_dir.next().unwrap().is_ok());
+            fail_reads.set(true);
+            let error = read_dir.find_map(|entry| entry.err());
+            assert_eq!(error, Some(Error::CORRUPTION));
+            assert!(read_dir.next().is_none());
+            assert!(read_dir.next().is_none());
+            fail_reads.set(false);
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_mount_with_buffers() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+
+    let mut alloc = Filesystem::allocate();
+    let mut read = [0; 700];
+    let mut prog = [0; 700];
+    let mut lookahead = [0; 16];
+
+    let buffers = MountBuffers::new(&mut read, &mut prog[..699], &mut lookahead);
+    assert_eq!(
+        Filesystem::mount_with_buffers(&mut alloc, &mut storage, buffers)
+            .map(drop)
+            .unwrap_err(),
+        Error::INVALID
+    );
+
+    let buffers = MountBuffers::new(&mut read, &mut prog, &mut lookahead);
+    let fs = Filesystem::mount_with_buffers(&mut alloc, &mut storage, buffers).unwrap();
+    fs.write(path!("file"), b"data").unwrap();
+    let contents: heapless::Vec<u8, 4> = fs.read(path!("file")).unwrap();
+    assert_eq!(&contents, b"data");
+    let _ = fs.into_inner();
+
+    assert!(read.iter().any(|&byte| byte != 0));
+    assert!(prog.iter().any(|&byte| byte != 0));
+}
+
+#[test]
+fn test_rename_returning() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("new"), b"new data")?;
+        assert_eq!(fs.rename_returning(path!("new"), path!("file"))?, None);
+        fs.write(path!("new"), b"newer data")?;
+        let replaced = fs.rename_returning(path!("new"), path!("file"))?.unwrap();
+        assert!(replaced.is_file());
+        assert_eq!(replaced.len(), 8);
+        assert_eq!(fs.metadata(path!("file"))?.len(), 10);
+        assert_eq!(
+            fs.rename_returning(path!("new"), path!("file")),
+            Err(Error::NO_SUCH_ENTRY)
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_peek() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("file"), b"MAGICdata")?;
+        fs.open_file_and_then(path!("file"), |file| {
+            let mut magic = [0; 5];
+            assert_eq!(file.peek(&mut magic)?, 5);
+            assert_eq!(&magic, b"MAGIC");
+            assert_eq!(file.seek(SeekFrom::Current(0))?, 0);
+
+            file.seek(SeekFrom::Start(5))?;
+            let mut buf = [0; 8];
+            assert_eq!(file.peek(&mut buf)?, 4);
+            assert_eq!(&buf[..4], b"data");
+            assert_eq!(file.seek(SeekFrom::Current(0))?, 5);
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_read_dir_sorted() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("dir"))?;
+        fs.write(path!("dir/b"), b"")?;
+        fs.write(path!("dir/a"), b"")?;
+        fs.create_dir(path!("dir/ab"))?;
+        fs.write(path!("dir/a.b"), b"")?;
+
+        let mut out: [PathBuf; 8] = Default::default();
+        let n = fs.read_dir_sorted(path!("dir"), &mut out)?;
+        let names: Vec<_> = out[..n].iter().map(|name| name.as_str()).collect();
+        assert_eq!(names, ["a.b", "ab", "a", "b"]);
+
+        let mut out: [PathBuf; 2] = Default::default();
+        assert_eq!(fs.read_dir_sorted(path!("dir"), &mut out)?, 2);
+        assert_eq!(
+            out,
+            [PathBuf::from(path!("a.b")), PathBuf::from(path!("ab"))]
+        );
+
+        let mut out: [PathBuf; 8] = Default::default();
+        let n = fs.read_dir_sorted_with_dots(path!("dir"), &mut out)?;
+        let names: Vec<_> = out[..n].iter().map(|name| name.as_str()).collect();
+        assert_eq!(names, ["..", ".", "a.b", "ab", "a", "b"]);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_stat() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let stat = fs.stat()?;
+        assert_eq!(
+            (stat.disk_version().major(), stat.disk_version().minor()),
+            (DISK_VERSION.major(), DISK_VERSION.minor())
+        );
+        assert_eq!(stat.block_size(), 700);
+        assert_eq!(stat.block_count(), 32);
+        assert_eq!(stat.block_count(), fs.total_blocks());
+        assert_eq!(stat.name_max(), 255);
+        assert_eq!(stat.file_max(), 2_147_483_647);
+        assert_eq!(stat.attr_max(), 1_022);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_create_with() {
+    let mut storage = CountingStorage::default();
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_with(path!("provisioning"), b"v1")?;
+        assert_eq!(fs.read::<10>(path!("provisioning"))?, b"v1");
+
+        // an existing file is not modified
+        assert_eq!(
+            fs.create_with(path!("provisioning"), b"v2"),
+            Err(Error::ENTRY_ALREADY_EXISTED)
+        );
+        assert_eq!(fs.read::<10>(path!("provisioning"))?, b"v1");
+
+        // a failed write does not leave a file behind
+        assert_eq!(
+            fs.create_with(path!("large"), &[0; 64 * 256]),
+            Err(Error::NO_SPACE)
+        );
+        assert!(!fs.exists(path!("large")));
+        fs.create_with(path!("large"), b"fits")?;
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[cfg(feature = "handle-tracking")]
+#[test]
+fn test_open_handles() {
+    use crate::fs::HandleKind;
+
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("dir"))?;
+        fs.write(path!("dir/file"), b"data")?;
+
+        let handles = || {
+            let mut handles = std::vec::Vec::new();
+            fs.open_handles(|path, kind| handles.push((PathBuf::from(path), kind)));
+            handles
+        };
+        assert!(handles().is_empty());
+
+        fs.read_dir_and_then(path!("dir"), |_| {
+            fs.open_file_and_then(path!("dir/file"), |_| {
+                assert_eq!(
+                    handles(),
+                    [
+                        (PathBuf::from(path!("dir")), HandleKind::Dir),
+                        (PathBuf::from(path!("dir/file")), HandleKind::File),
+                    ]
+                );
+                Ok(())
+            })
+        })?;
+        assert!(handles().is_empty());
+
+        // a leaked file stays registered
+        let mut alloc = File::allocate();
+        let file = unsafe { File::open(fs, &mut alloc, path!("dir/file"))? };
+        assert_eq!(
+            handles(),
+            [(PathBuf::from(path!("dir/file")), HandleKind::File)]
+        );
+        unsafe { file.close()? };
+        assert!(handles().is_empty());
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_link() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("firmware"))?;
+        fs.write(path!("firmware/v2.bin"), b"image")?;
+        fs.create_link(path!("current"), path!("firmware/v2.bin"))?;
+
+        assert_eq!(fs.read_link(path!("current"))?, path!("firmware/v2.bin"));
+        // links are regular files
+        assert_eq!(fs.read::<20>(path!("current"))?, b"firmware/v2.bin");
+        assert_eq!(
+            fs.create_link(path!("current"), path!("firmware/v1.bin")),
+            Err(Error::ENTRY_ALREADY_EXISTED)
+        );
+
+        assert_eq!(fs.read_link(path!("firmware/v2.bin")), Err(Error::INVALID));
+        assert_eq!(fs.read_link(path!("firmware")), Err(Error::INVALID));
+        assert_eq!(fs.read_link(path!("missing")), Err(Error::NO_SUCH_ENTRY));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_hash_file() {
+    use core::hash::Hasher as _;
+
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let data: std::vec::Vec<u8> = (0..2000).map(|i| i as u8).collect();
+        fs.write(path!("image"), &data)?;
+
+        let mut chunks = std::vec::Vec::new();
+        assert_eq!(
+            fs.hash_file(path!("image"), |chunk| chunks.extend_from_slice(chunk))?,
+            2000
+        );
+        assert_eq!(chunks, data);
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        fs.hash_file(path!("image"), |chunk| hasher.write(chunk))?;
+        let mut expected = std::collections::hash_map::DefaultHasher::new();
+        expected.write(&data);
+        assert_eq!(hasher.finish(), expected.finish());
+
+        fs.write(path!("empty"), &[])?;
+        assert_eq!(fs.hash_file(path!("empty"), |_| panic!())?, 0);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_read_tail() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let log: std::vec::Vec<u8> = (0..3000).map(|i| i as u8).collect();
+        fs.write(path!("log"), &log)?;
+
+        let mut buf = [0; 100];
+        assert_eq!(fs.read_tail(path!("log"), &mut buf)?, 100);
+        assert_eq!(buf[..], log[2900..]);
+
+        fs.write(path!("short"), b"last line")?;
+        assert_eq!(fs.read_tail(path!("short"), &mut buf)?, 9);
+        assert_eq!(&buf[..9], b"last line");
+
+        fs.write(path!("empty"), &[])?;
+        assert_eq!(fs.read_tail(path!("empty"), &mut buf)?, 0);
+        assert_eq!(fs.read_tail(path!("log"), &mut [])?, 0);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_read_dir_since() {
+    use crate::{consts::TIMESTAMP_ATTRIBUTE_ID, fs::MissingTimestamp};
+
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("data"))?;
+        for (path, timestamp) in [
+            (path!("data/old"), 100u64),
+            (path!("data/synced"), 200),
+            (path!("data/new"), 300),
+        ] {
+            fs.write(path, b"data")?;
+            fs.set_attribute(path, TIMESTAMP_ATTRIBUTE_ID, &timestamp.to_le_bytes())?;
+        }
+        fs.create_dir(path!("data/subdir"))?;
+        fs.set_attribute(
+            path!("data/subdir"),
+            TIMESTAMP_ATTRIBUTE_ID,
+            &400u64.to_le_bytes(),
+        )?;
+        fs.write(path!("data/untracked"), b"data")?;
+        fs.write(path!("data/invalid"), b"data")?;
+        fs.set_attribute(path!("data/invalid"), TIMESTAMP_ATTRIBUTE_ID, &[1; 4])?;
+
+        let since = |missing| {
+            let mut paths = std::vec::Vec::new();
+            fs.read_dir_since(path!("data"), 200, missing, |path| {
+                paths.push(path.as_str().to_owned())
+            })
+            .map(|_| paths)
+        };
+        assert_eq!(since(MissingTimestamp::Skip)?, ["data/new", "data/subdir"]);
+        assert_eq!(
+            since(MissingTimestamp::Include)?,
+            ["data/invalid", "data/new", "data/subdir", "data/untracked"]
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_file_blocks() {
+    let mut storage = crate::testing::RamStorage::<256, 64>::new();
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let blocks = |path| {
+            let mut blocks = std::vec::Vec::new();
+            fs.file_blocks(path, |block| blocks.push(block))
+                .map(|_| blocks)
+        };
+
+        fs.write(path!("inline"), b"small")?;
+        assert!(blocks(path!("inline"))?.is_empty());
+
+        fs.write(path!("large"), &[0x42; 2000])?;
+        let large = blocks(path!("large"))?;
+        assert_eq!(large.len(), fs.blocks_used_by(path!("large"))?);
+        let mut unique = large.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), large.len());
+        assert!(large.iter().all(|&block| (2..64).contains(&block)));
+
+        fs.write(path!("other"), &[0x42; 1000])?;
+        let other = blocks(path!("other"))?;
+        assert_eq!(other.len(), 4);
+        assert!(other.iter().all(|block| !large.contains(block)));
+
+        fs.create_dir(path!("dir"))?;
+        assert_eq!(blocks(path!("dir")), Err(Error::PATH_IS_DIR));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_rename_safe() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("config"), b"old")?;
+        fs.write(path!("config.new"), b"new")?;
+        fs.rename_safe(path!("config.new"), path!("config"))?;
+        assert_eq!(fs.read::<10>(path!("config"))?, b"new");
+        assert!(!fs.exists(path!("config.new")));
+        assert!(!fs.exists(path!("config.bak")));
+
+        // the previous entry is restored if the rename fails
+        assert_eq!(
+            fs.rename_safe(path!("missing"), path!("config")),
+            Err(Error::NO_SUCH_ENTRY)
+        );
+        assert_eq!(fs.read::<10>(path!("config"))?, b"new");
+        assert!(!fs.exists(path!("config.bak")));
+
+        // non-empty directories can be replaced
+        fs.create_dir(path!("data"))?;
+        fs.write(path!("data/file"), b"old")?;
+        fs.create_dir(path!("staging"))?;
+        fs.write(path!("staging/file"), b"new")?;
+        fs.rename_safe(path!("staging"), path!("data"))?;
+        assert_eq!(fs.read::<10>(path!("data/file"))?, b"new");
+        assert!(!fs.exists(path!("data.bak")));
+
+        // existing backups are not replaced
+        fs.write(path!("config.bak"), b"user data")?;
+        fs.write(path!("config.new"), b"newer")?;
+        assert_eq!(
+            fs.rename_safe(path!("config.new"), path!("config")),
+            Err(Error::ENTRY_ALREADY_EXISTED)
+        );
+        assert_eq!(fs.read::<10>(path!("config"))?, b"new");
+        assert_eq!(fs.read::<10>(path!("config.bak"))?, b"user data");
+        assert!(fs.exists(path!("config.new")));
+
+        fs.write(path!("fresh"), b"data")?;
+        fs.rename_safe(path!("fresh"), path!("moved"))?;
+        assert!(fs.exists(path!("moved")));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_read_struct() {
+    #[derive(Debug, PartialEq)]
+    #[repr(C)]
+    struct Record {
+        id: u32,
+        flags: u16,
+        tag: [u8; 2],
+    }
+
+    unsafe impl crate::io::FromBytes for Record {}
+
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let mut data = std::vec::Vec::new();
+        for id in 0..2u32 {
+            data.extend_from_slice(&id.to_ne_bytes());
+            data.extend_from_slice(&0x0102u16.to_ne_bytes());
+            data.extend_from_slice(b"ok");
+        }
+        data.extend_from_slice(&[0; 4]);
+        fs.write(path!("records"), &data)?;
+
+        fs.open_file_and_then(path!("records"), |file| {
+            for id in 0..2 {
+                let record: Record = file.read_struct()?;
+                assert_eq!(
+                    record,
+                    Record {
+                        id,
+                        flags: 0x0102,
+                        tag: *b"ok"
+                    }
+                );
+            }
+            // short read
+            assert_eq!(file.read_struct::<Record>(), Err(Error::IO));
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_walk_dir() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        for dir in [path!("b"), path!("a"), path!("a/d"), path!("a/c")] {
+            fs.create_dir(dir)?;
+        }
+        for file in [path!("z"), path!("a/d/y"), path!("a/c/x"), path!("a/e")] {
+            fs.write(file, b"data")?;
+        }
+
+        let walk = |sorted| {
+            let mut paths = std::vec::Vec::new();
+            let f = |entry: &DirEntry| paths.push(entry.path().as_str().to_owned());
+            if sorted {
+                fs.walk_dir_sorted::<4>(path!("/"), f)
+            } else {
+                fs.walk_dir(path!("/"), f)
+            }
+            .map(|_| paths)
+        };
+        let sorted = walk(true)?;
+        assert_eq!(
+            sorted,
+            ["/a", "/a/c", "/a/c/x", "/a/d", "/a/d/y", "/a/e", "/b", "/z"]
+        );
+        let mut native = walk(false)?;
+        native.sort();
+        assert_eq!(native, sorted);
+
+        for i in 0..5 {
+            fs.write(
+                &PathBuf::try_from(format!("b/{i}").as_str()).unwrap(),
+                b"data",
+            )?;
+        }
+        assert_eq!(walk(true), Err(Error::NO_MEMORY));
+        assert_eq!(walk(false)?.len(), 13);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_copy_attributes() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let mut buffer = [0; Attribute::MAX_SIZE as _];
+        fs.write(path!("template"), b"template")?;
+        fs.set_attribute(path!("template"), 0, b"owner")?;
+        fs.set_attribute(path!("template"), 200, &[0xaa; 100])?;
+        fs.write(path!("new"), b"contents")?;
+        fs.set_attribute(path!("new"), 0, b"previous owner")?;
+        fs.set_attribute(path!("new"), 1, b"kept")?;
+
+        fs.copy_attributes(path!("template"), path!("new"))?;
+        assert_eq!(fs.read::<10>(path!("new"))?, b"contents");
+        let attribute = fs.attribute(path!("new"), 0, &mut buffer)?.unwrap();
+        assert_eq!(attribute.data(), b"owner");
+        let attribute = fs.attribute(path!("new"), 200, &mut buffer)?.unwrap();
+        assert_eq!(attribute.data(), &[0xaa; 100]);
+        let attribute = fs.attribute(path!("new"), 1, &mut buffer)?.unwrap();
+        assert_eq!(attribute.data(), b"kept");
+
+        assert_eq!(
+            fs.copy_attributes(path!("missing"), path!("new")),
+            Err(Error::NO_SUCH_ENTRY)
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_reset_file() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let mut buffer = [0; 16];
+        fs.write(path!("log"), &[0x42; 2000])?;
+        for id in [0, 1, 255] {
+            fs.set_attribute(path!("log"), id, b"attribute")?;
+        }
+
+        fs.reset_file(path!("log"))?;
+        assert_eq!(fs.metadata(path!("log"))?.len(), 0);
+        for id in [0, 1, 255] {
+            assert!(fs.attribute(path!("log"), id, &mut buffer)?.is_none());
+        }
+
+        assert_eq!(fs.reset_file(path!("missing")), Err(Error::NO_SUCH_ENTRY));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_raw() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("file"), &[0x42; 2000])?;
+        let size = unsafe { crate::ll::lfs_fs_size(fs.raw()) };
+        assert!(size > 0);
+        assert_eq!(size as usize, fs.total_blocks() - fs.available_blocks()?);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_flush() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_file_and_then(path!("log"), |file| {
+            fn write_record(writer: &impl Write, record: &[u8]) -> Result<()> {
+                writer.write_all(record)?;
+                writer.flush()
+            }
+
+            write_record(file, b"first")?;
+            assert_eq!(fs.read::<10>(path!("log"))?, b"first");
+            file.write_all(b" second")?;
+            assert_eq!(fs.read::<20>(path!("log"))?, b"first");
+            file.flush()?;
+            assert_eq!(fs.read::<20>(path!("log"))?, b"first second");
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_attribute_limit() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let mut buffer = [0; 100];
+        fs.write(path!("file"), b"data")?;
+
+        // with 700 byte blocks, six attributes of 100 bytes fit
+        for id in 0..6 {
+            fs.set_attribute(path!("file"), id, &[id; 100])?;
+        }
+        assert_eq!(
+            fs.set_attribute(path!("file"), 6, &[6; 100]),
+            Err(Error::NO_SPACE)
+        );
+        assert!(fs.attribute(path!("file"), 6, &mut buffer)?.is_none());
+        for id in 0..6 {
+            let attribute = fs.attribute(path!("file"), id, &mut buffer)?.unwrap();
+            assert_eq!(attribute.data(), &[id; 100]);
+        }
+        assert_eq!(fs.read::<10>(path!("file"))?, b"data");
+
+        // other entries are not affected
+        fs.write(path!("other"), b"data")?;
+        fs.set_attribute(path!("other"), 0, &[0; 100])?;
+
+        fs.remove_attribute(path!("file"), 0)?;
+        fs.set_attribute(path!("file"), 6, &[6; 100])?;
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_serialize_tree() {
+    struct Stream(core::cell::RefCell<std::vec::Vec<u8>>, Cell<usize>);
+
+    impl Write for Stream {
+        fn write(&self, data: &[u8]) -> Result<usize> {
+            self.0.borrow_mut().extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for Stream {
+        fn read(&self, buf: &mut [u8]) -> Result<usize> {
+            let data = self.0.borrow();
+            let n = buf.len().min(data.len() - self.1.get());
+            buf[..n].copy_from_slice(&data[self.1.get()..][..n]);
+            self.1.set(self.1.get() + n);
+            Ok(n)
+        }
+    }
+
+    let stream = Stream(Default::default(), Cell::new(0));
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir_all(path!("backup/config/empty"))?;
+        fs.write(path!("backup/config/app.toml"), b"debug = true")?;
+        fs.write(path!("backup/image"), &[0x42; 2000])?;
+        fs.write(path!("backup/empty"), &[])?;
+        fs.write(path!("outside"), b"data")?;
+        fs.serialize_tree(path!("backup"), &stream)?;
+
+        fs.create_dir(path!("restore"))?;
+        fs.deserialize_tree(path!("restore"), &stream)?;
+        assert_eq!(
+            fs.read::<20>(path!("restore/config/app.toml"))?,
+            b"debug = true"
+        );
+        assert_eq!(fs.read::<2000>(path!("restore/image"))?, &[0x42; 2000][..]);
+        assert_eq!(fs.metadata(path!("restore/empty"))?.len(), 0);
+        assert!(fs.metadata(path!("restore/config/empty"))?.is_dir());
+        assert_eq!(fs.count_dir(path!("restore"))?, 3);
+        Ok(())
+    })
+    .unwrap();
+
+    let data = stream.0.borrow();
+    assert_eq!(data.last(), Some(&0));
+    assert_eq!(&data[..8], b"\x02\x06config");
+
+    // paths escaping the root are rejected
+    let stream = Stream(
+        core::cell::RefCell::new(b"\x02\x02..\x00".to_vec()),
+        Cell::new(0),
+    );
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("restore"))?;
+        assert_eq!(
+            fs.deserialize_tree(path!("restore"), &stream),
+            Err(Error::INVALID)
+        );
+
+        // path lengths larger than PathBuf::MAX_SIZE are rejected
+        let mut data = b"\x02\xff".to_vec();
+        data.extend_from_slice(&[b'a'; 255]);
+        data.push(0);
+        let stream = Stream(core::cell::RefCell::new(data), Cell::new(0));
+        assert_eq!(
+            fs.deserialize_tree(path!("restore"), &stream),
+            Err(Error::INVALID)
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_read_dir_open_file() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("/dir"))?;
+        fs.write(path!("/dir/a"), b"first")?;
+        fs.write(path!("/dir/b"), b"second")?;
+        fs.write(path!("/outside"), b"outside")?;
+
+        fs.read_dir_and_then(path!("/dir"), |read_dir| {
+            let mut contents: heapless::Vec<heapless::Vec<u8, 8>, 2> = Default::default();
+            while let Some(entry) = read_dir.next() {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let data = read_dir.open_file_and_then(entry.file_name(), |file| {
+                    let mut buf = [0; 8];
+                    let n = file.read(&mut buf)?;
+                    Ok(heapless::Vec::from_slice(&buf[..n]).unwrap())
+                })?;
+                contents.push(data).unwrap();
+            }
+            assert_eq!(contents[0], b"first");
+            assert_eq!(contents[1], b"second");
+
+            read_dir.open_file_with_options_and_then(
+                |options| options.write(true).truncate(true),
+                path!("a"),
+                |file| file.write(b"new"),
+            )?;
+
+            assert_eq!(
+                read_dir.open_file_and_then(path!("../outside"), |_| Ok(())),
+                Err(Error::INVALID)
+            );
+            assert_eq!(
+                read_dir.open_file_and_then(path!("/outside"), |_| Ok(())),
+                Err(Error::INVALID)
+            );
+            assert_eq!(
+                read_dir.open_file_and_then(path!("missing"), |_| Ok(())),
+                Err(Error::NO_SUCH_ENTRY)
+            );
+            Ok(())
+        })?;
+
+        let data: heapless::Vec<u8, 8> = fs.read(path!("/dir/a"))?;
+        assert_eq!(data, b"new");
+        Ok(())
+    })
+    .unwrap();
+}
+
+/// Storage that can tear the next write, like a power loss while programming, and flip a bit in
+/// the first commit of some blocks when reading them.
+struct FlakyStorage<'a> {
+    inner: crate::testing::RamStorage<256, 64>,
+    tear_write: &'a Cell<bool>,
+    flip_blocks: &'a Cell<Option<[u32; 2]>>,
+}
+
+impl crate::driver::Storage for FlakyStorage<'_> {
+    const READ_SIZE: usize = 1;
+    const WRITE_SIZE: usize = 1;
+    const BLOCK_SIZE: usize = 256;
+    const BLOCK_COUNT: usize = 64;
+    type CACHE_SIZE = consts::U32;
+    type LOOKAHEAD_SIZE = consts::U1;
+
+    fn read(&mut self, off: usize, buf: &mut [u8]) -> Result<usize> {
+        let n = self.inner.read(off, buf)?;
+        if let Some(blocks) = self.flip_blocks.get() {
+            for (off, byte) in (off..).zip(buf.iter_mut()) {
+                if blocks.contains(&((off / 256) as u32)) && off % 256 == 8 {
+                    *byte ^= 0x10;
+                }
+            }
+        }
+        Ok(n)
+    }
+
+    fn write(&mut self, off: usize, data: &[u8]) -> Result<usize> {
+        if self.tear_write.replace(false) {
+            self.inner.write(off, &data[..data.len() / 2])?;
+            return Err(Error::IO);
+        }
+        self.inner.write(off, data)
+    }
+
+    fn erase(&mut self, off: usize, len: usize) -> Result<usize> {
+        self.inner.erase(off, len)
+    }
+}
+
+#[test]
+fn test_verify_metadata() {
+    let tear_write = Cell::new(false);
+    let flip_blocks = Cell::new(None);
+    let mut storage = FlakyStorage {
+        inner: Default::default(),
+        tear_write: &tear_write,
+        flip_blocks: &flip_blocks,
+    };
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.verify_metadata()?;
+        fs.create_dir(path!("/dir"))?;
+        fs.write(path!("/dir/a"), b"first")?;
+        fs.write(path!("/dir/b"), &[0x42; 600])?;
+        fs.write(path!("/c"), b"second")?;
+        fs.remove(path!("/dir/a"))?;
+        fs.verify_metadata()
+    })
+    .unwrap();
+
+    // a torn commit is ignored like littlefs does
+    let before = storage.inner.as_bytes().to_vec();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        tear_write.set(true);
+        assert_eq!(fs.write(path!("/d"), b"torn"), Err(Error::IO));
+        Ok(())
+    })
+    .unwrap();
+    assert_ne!(storage.inner.as_bytes(), &before[..]);
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.verify_metadata()?;
+        assert!(!fs.exists(path!("/d")));
+        assert_eq!(fs.read::<10>(path!("/c"))?, b"second");
+        Ok(())
+    })
+    .unwrap();
+
+    // a bit flip in the first commit of both blocks of a pair
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let pair = unsafe {
+            let mut dir: crate::ll::lfs_dir_t = core::mem::zeroed();
+            assert_eq!(
+                crate::ll::lfs_dir_open(fs.raw(), &mut dir, path!("/dir").as_ptr()),
+                0
+            );
+            let pair = dir.m.pair;
+            assert_eq!(crate::ll::lfs_dir_close(fs.raw(), &mut dir), 0);
+            pair
+        };
+        flip_blocks.set(Some(pair));
+        assert_eq!(fs.verify_metadata(), Err(Error::CORRUPTION));
+        // the inactive block is only used if the active one has no valid commit
+        flip_blocks.set(Some([pair[1], pair[1]]));
+        fs.verify_metadata()?;
+        flip_blocks.set(None);
+        fs.verify_metadata()
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_hash_range() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let data: std::vec::Vec<u8> = (0..2000).map(|i| i as u8).collect();
+        fs.write(path!("image"), &data)?;
+
+        fs.open_file_and_then(path!("image"), |file| {
+            // resume in several sessions
+            let mut hashed = std::vec::Vec::new();
+            let mut offset = 0;
+            while offset < data.len() {
+                let n = file.hash_range(offset, 750, |chunk| hashed.extend_from_slice(chunk))?;
+                assert_eq!(n, core::cmp::min(750, data.len() - offset));
+                offset += n;
+            }
+            assert_eq!(hashed, data);
+
+            let mut chunks = std::vec::Vec::new();
+            assert_eq!(
+                file.hash_range(10, 20, |chunk| chunks.extend_from_slice(chunk))?,
+                20
+            );
+            assert_eq!(chunks, &data[10..30]);
+            assert_eq!(file.seek(SeekFrom::Current(0))?, 30);
+
+            // the range is clamped to the end of the file
+            chunks.clear();
+            assert_eq!(
+                file.hash_range(1990, usize::MAX, |chunk| chunks.extend_from_slice(chunk))?,
+                10
+            );
+            assert_eq!(chunks, &data[1990..]);
+            assert_eq!(file.hash_range(2000, 1, |_| panic!())?, 0);
+            assert_eq!(file.hash_range(5000, 1, |_| panic!())?, 0);
+            assert_eq!(file.hash_range(0, 0, |_| panic!())?, 0);
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_config_summary() {
+    let mut storage = CountingStorage::default();
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let config = fs.config_summary();
+        assert_eq!(config.read_size(), 1);
+        assert_eq!(config.prog_size(), 1);
+        assert_eq!(config.block_size(), 256);
+        assert_eq!(config.block_count(), 64);
+        assert_eq!(config.block_cycles(), -1);
+        assert_eq!(config.cache_size(), 32);
+        assert_eq!(config.lookahead_size(), 8);
+        assert_eq!(config.name_max(), 255);
+        assert_eq!(config.attr_max(), 1_022);
+        assert_eq!(
+            format!("{config}"),
+            "read_size=1 prog_size=1 block_size=256 block_count=64 block_cycles=-1 \
+             cache_size=32 lookahead_size=8 name_max=255 file_max=2147483647 attr_max=1022"
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_read_superblock() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    assert_eq!(
+        Filesystem::read_superblock::<701>(&mut storage),
+        Err(Error::INVALID)
+    );
+    assert_eq!(
+        Filesystem::read_superblock::<50>(&mut storage),
+        Err(Error::INVALID)
+    );
+
+    Filesystem::format(&mut storage).unwrap();
+    let blocks = Filesystem::read_superblock::<100>(&mut storage).unwrap();
+    let current = blocks
+        .iter()
+        .max_by_key(|block| u32::from_le_bytes(block[..4].try_into().unwrap()))
+        .unwrap();
+    assert_eq!(&current[8..16], b"littlefs");
+    let block_size = u32::from_le_bytes(current[24..28].try_into().unwrap());
+    let block_count = u32::from_le_bytes(current[28..32].try_into().unwrap());
+    assert_eq!((block_size, block_count), (700, 32));
+}
+
+#[derive(Default)]
+struct CountingStorage {
+    inner: crate::testing::RamStorage<256, 64>,
+    writes: usize,
+    erases: usize,
+}
+
+impl crate::driver::Storage for CountingStorage {
+    const READ_SIZE: usize = 1;
+    const WRITE_SIZE: usize = 1;
+    const BLOCK_SIZE: usize = 256;
+    const BLOCK_COUNT: usize = 64;
+    type CACHE_SIZE = consts::U32;
+    type LOOKAHEAD_SIZE = consts::U1;
+
+    fn read(&mut self, off: usize, buf: &mut [u8]) -> Result<usize> {
+        self.inner.read(off, buf)
+    }
+
+    fn write(&mut self, off: usize, data: &[u8]) -> Result<usize> {
+        self.writes += 1;
+        self.inner.write(off, data)
+    }
+
+    fn erase(&mut self, off: usize, len: usize) -> Result<usize> {
+        self.erases += 1;
+        self.inner.erase(off, len)
+    }
+}
+
+#[test]
+fn test_read_only_no_writes() {
+    let mut storage = CountingStorage::default();
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("dir"))?;
+        fs.write(path!("dir/file"), &[0x42; 1000])?;
+        fs.set_attribute(path!("dir/file"), 1, b"attribute")
+    })
+    .unwrap();
+
+    storage.writes = 0;
+    storage.erases = 0;
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let mut buf = [0; 1000];
+        fs.open_file_and_then(path!("dir/file"), |file| {
+            file.seek(SeekFrom::Start(100))?;
+            file.read_exact(&mut buf[..900])?;
+            Ok(())
+        })?;
+        let contents: heapless::Vec<u8, 1000> = fs.read(path!("dir/file"))?;
+        assert_eq!(contents.len(), 1000);
+        fs.metadata(path!("dir/file"))?;
+        fs.read_dir_and_then(path!("dir"), |read_dir| {
+            read_dir.try_for_each(|entry| entry.map(drop))
+        })?;
+        let mut attribute = [0; 16];
+        fs.attribute(path!("dir/file"), 1, &mut attribute)?;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!((storage.writes, storage.erases), (0, 0));
+}
+
+#[test]
+fn test_max_file_name() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let name = [b'a'; crate::consts::PATH_MAX];
+        let path = PathBuf::try_from(&name[..]).unwrap();
+        assert!(path.as_str().len() < crate::consts::FILENAME_MAX_PLUS_ONE as usize);
+        fs.write(&path, b"data")?;
+        assert_eq!(fs.read::<4>(&path)?, b"data");
+        assert!(PathBuf::try_from(&[b'a'; crate::consts::PATH_MAX + 1][..]).is_err());
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_glob() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("logs"))?;
+        fs.create_dir(path!("logs/log.old"))?;
+        for path in [
+            path!("logs/log.1"),
+            path!("logs/log.2"),
+            path!("logs/log"),
+            path!("logs/other.1"),
+        ] {
+            fs.write(path, b"")?;
+        }
+        let mut matches = Vec::new();
+        fs.glob(path!("logs"), "log.*", |path| {
+            matches.push(PathBuf::from(path))
+        })?;
+        assert_eq!(
+            matches,
+            [
+                PathBuf::from(path!("logs/log.1")),
+                PathBuf::from(path!("logs/log.2"))
+            ]
+        );
+
+        let mut count = 0;
+        fs.glob(path!("logs"), "*.?", |_| count += 1)?;
+        assert_eq!(count, 3);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_gc() {
+    let mut storage = CountingStorage::default();
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        for i in 0..20u8 {
+            fs.write(path!("file"), &[i; 100])?;
+        }
+        fs.gc()?;
+        fs.gc()?;
+        assert_eq!(fs.read::<100>(path!("file"))?, [19; 100]);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_append_file() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.append_file_and_then(path!("log"), |file| file.write_all(b"one,"))?;
+        fs.append_file_and_then(path!("log"), |file| {
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(b"two")
+        })?;
+        assert_eq!(fs.read::<16>(path!("log"))?, b"one,two");
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_read_uninit() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("file"), b"some data")?;
+        fs.open_file_and_then(path!("file"), |file| {
+            let mut buf = [core::mem::MaybeUninit::uninit(); 16];
+            assert_eq!(file.read_uninit(&mut buf[..4])?, b"some");
+            assert_eq!(file.read_uninit(&mut buf)?, b" data");
+            assert!(file.read_uninit(&mut buf)?.is_empty());
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+struct BadGeometryStorage;
+
+impl crate::driver::Storage for BadGeometryStorage {
+    const READ_SIZE: usize = 16;
+    const WRITE_SIZE: usize = 24;
+    const BLOCK_SIZE: usize = 256;
+    const BLOCK_COUNT: usize = 64;
+    type CACHE_SIZE = consts::U32;
+    type LOOKAHEAD_SIZE = consts::U1;
+
+    fn read(&mut self, _off: usize, _buf: &mut [u8]) -> Result<usize> {
+        Err(Error::IO)
+    }
+
+    fn write(&mut self, _off: usize, _data: &[u8]) -> Result<usize> {
+        Err(Error::IO)
+    }
+
+    fn erase(&mut self, _off: usize, _len: usize) -> Result<usize> {
+        Err(Error::IO)
+    }
+}
+
+#[test]
+fn test_validate_geometry() {
+    use crate::driver::{GeometryError, Storage as _};
+
+    assert_eq!(
+        BadGeometryStorage.validate_geometry(),
+        Err(GeometryError::CacheSizeNotMultipleOfReadWriteSize)
+    );
+    assert_eq!(
+        crate::testing::RamStorage::<256, 4>::new().validate_geometry(),
+        Ok(())
+    );
+    let mut backend = Ram::default();
+    assert_eq!(RamStorage::new(&mut backend).validate_geometry(), Ok(()));
+}
+
+/// Storage with logical blocks of two 128 byte erase units.
+#[derive(Default)]
+struct SubBlockEraseStorage {
+    inner: crate::testing::RamStorage<128, 64>,
+    erases: usize,
+}
+
+impl crate::driver::Storage for SubBlockEraseStorage {
+    const READ_SIZE: usize = 1;
+    const WRITE_SIZE: usize = 1;
+    const BLOCK_SIZE: usize = 256;
+    const ERASE_SIZE: usize = 128;
+    const BLOCK_COUNT: usize = 32;
+    type CACHE_SIZE = consts::U32;
+    type LOOKAHEAD_SIZE = consts::U1;
+
+    fn read(&mut self, off: usize, buf: &mut [u8]) -> Result<usize> {
+        self.inner.read(off, buf)
+    }
+
+    fn write(&mut self, off: usize, data: &[u8]) -> Result<usize> {
+        self.inner.write(off, data)
+    }
+
+    fn erase(&mut self, off: usize, len: usize) -> Result<usize> {
+        assert_eq!(off % 128, 0);
+        assert_eq!(len, 128);
+        self.erases += 1;
+        self.inner.erase(off, len)
+    }
+}
+
+#[test]
+fn test_erase_size() {
+    use crate::driver::Storage as _;
+
+    let mut storage = SubBlockEraseStorage::default();
+    assert_eq!(storage.validate_geometry(), Ok(()));
+    // the superblock pair is erased again by `format`
+    Filesystem::format_erased(&mut storage).unwrap();
+    assert_eq!(storage.erases, 64 + 2 * 2);
+
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        assert_eq!(fs.total_blocks(), 32);
+        fs.write(path!("file"), &[0x42; 1000])?;
+        assert_eq!(fs.read::<1000>(path!("file"))?, &[0x42; 1000][..]);
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_write_verified() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("boot"), b"slot=")?;
+        fs.open_file_with_options_and_then(
+            |options| options.read(true).write(true).append(true),
+            path!("boot"),
+            |file| {
+                file.write_verified(&[b'a'; 100])?;
+                assert_eq!(file.seek(SeekFrom::Current(0))?, 105);
+                Ok(())
+            },
+        )?;
+        assert_eq!(fs.read::<105>(path!("boot"))?.len(), 105);
+        Ok(())
+    })
+    .unwrap();
+}
+
+/// Storage that, once armed, flips the data read from blocks other than the superblock pair
+/// after the next write to the superblock pair, for example by a sync in the root directory.
+struct DecayingStorage<'a> {
+    inner: crate::testing::RamStorage<256, 64>,
+    armed: &'a Cell<bool>,
+    decayed: bool,
+}
+
+impl crate::driver::Storage for DecayingStorage<'_> {
+    const READ_SIZE: usize = 1;
+    const WRITE_SIZE: usize = 1;
+    const BLOCK_SIZE: usize = 256;
+    const BLOCK_COUNT: usize = 64;
+    type CACHE_SIZE = consts::U32;
+    type LOOKAHEAD_SIZE = consts::U1;
+
+    fn read(&mut self, off: usize, buf: &mut [u8]) -> Result<usize> {
+        let n = self.inner.read(off, buf)?;
+        if self.decayed && off >= 2 * 256 {
+            buf.iter_mut().for_each(|byte| *byte ^= 0x01);
+        }
+        Ok(n)
+    }
+
+    fn write(&mut self, off: usize, data: &[u8]) -> Result<usize> {
+        if self.armed.get() && off < 2 * 256 {
+            self.decayed = true;
+        }
+        self.inner.write(off, data)
+    }
+
+    fn erase(&mut self, off: usize, len: usize) -> Result<usize> {
+        self.inner.erase(off, len)
+    }
+}
+
+#[test]
+fn test_write_verified_mismatch() {
+    let armed = Cell::new(false);
+    let mut storage = DecayingStorage {
+        inner: Default::default(),
+        armed: &armed,
+        decayed: false,
+    };
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("boot"), b"slot=")?;
+        fs.open_file_with_options_and_then(
+            |options| options.read(true).write(true).append(true),
+            path!("boot"),
+            |file| {
+                // the data is programmed and checked by littlefs, but reads back differently
+                // after the sync
+                armed.set(true);
+                assert_eq!(file.write_verified(&[b'a'; 100]), Err(Error::CORRUPTION));
+                Ok(())
+            },
+        )
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_count_dir() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("dir"))?;
+        assert_eq!(fs.count_dir(path!("dir"))?, 0);
+        fs.create_dir(path!("dir/sub"))?;
+        fs.write(path!("dir/file"), b"")?;
+        fs.write(path!("dir/sub/file"), b"")?;
+        assert_eq!(fs.count_dir(path!("dir"))?, 2);
+        assert_eq!(fs.count_dir(path!("/"))?, 1);
+        assert_eq!(fs.count_dir(path!("missing")), Err(Error::NO_SUCH_ENTRY));
+        Ok(())
+    })
+    .unwrap();
+}
+
+/// Looks up an entry in a simple archive format:  a `u32` entry count, followed by a table of
+/// entries with an 8-byte name, a `u32` offset and a `u32` length each, followed by the data.
+fn find_archive_entry<R: Read + Seek>(
+    reader: &R,
+    name: &[u8; 8],
+    buf: &mut [u8],
+) -> Result<Option<usize>> {
+    let mut word = [0; 4];
+    reader.seek(SeekFrom::Start(0))?;
+    reader.read_exact(&mut word)?;
+    let count = u32::from_le_bytes(word);
+    for i in 0..count {
+        reader.seek(SeekFrom::Start(4 + i * 16))?;
+        let mut entry = [0; 16];
+        reader.read_exact(&mut entry)?;
+        if &entry[..8] == name {
+            let offset = u32::from_le_bytes(entry[8..12].try_into().unwrap());
+            let len = u32::from_le_bytes(entry[12..].try_into().unwrap()) as usize;
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut buf[..len])?;
+            return Ok(Some(len));
+        }
+    }
+    Ok(None)
+}
+
+#[test]
+fn test_generic_read_seek() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let entries: [(&[u8; 8], &[u8]); 3] = [
+            (b"first\0\0\0", b"one"),
+            (b"second\0\0", &[2; 500]),
+            (b"third\0\0\0", b"three"),
+        ];
+        fs.create_file_and_then(path!("archive"), |file| {
+            file.write_all(&(entries.len() as u32).to_le_bytes())?;
+            let mut offset = 4 + 16 * entries.len();
+            for (name, data) in entries {
+                file.write_all(name)?;
+                file.write_all(&(offset as u32).to_le_bytes())?;
+                file.write_all(&(data.len() as u32).to_le_bytes())?;
+                offset += data.len();
+            }
+            for (_, data) in entries {
+                file.write_all(data)?;
+            }
+            Ok(())
+        })?;
+
+        fs.open_file_and_then(path!("archive"), |file| {
+            let mut buf = [0; 500];
+            for (name, data) in entries.iter().rev() {
+                let len = find_archive_entry(file, name, &mut buf)?;
+                assert_eq!(&buf[..len.unwrap()], *data);
+            }
+            assert_eq!(find_archive_entry(file, b"missing\0", &mut buf)?, None);
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_write_no_space() {
+    let mut storage = crate::testing::RamStorage::<256, 8>::new();
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        let result = fs.create_file_and_then(path!("log"), |file| {
+            for _ in 0..100 {
+                file.write_all(&[0x42; 64])?;
+            }
+            Ok(())
+        });
+        assert_eq!(result, Err(Error::NO_SPACE));
+        let result = fs.write(path!("log"), &[0x42; 4096]);
+        assert_eq!(result, Err(Error::NO_SPACE));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_is_root_path() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("a"))?;
+        for path in [
+            path!("/"),
+            path!(""),
+            path!("."),
+            path!("//"),
+            path!("/./"),
+            path!("/a/.."),
+        ] {
+            assert!(fs.is_root_path(path), "{path}");
+            assert_eq!(fs.count_dir(path)?, 1);
+        }
+        for path in [path!("/a"), path!("a/."), path!("a/../a")] {
+            assert!(!fs.is_root_path(path), "{path}");
+        }
+
+        let mount_point = path!("/mnt/data");
+        let relative = path!("/mnt/data/a").strip_prefix(mount_point).unwrap();
+        assert!(fs.metadata(relative)?.is_dir());
+        let relative = path!("/mnt/data").strip_prefix(mount_point).unwrap();
+        assert!(fs.is_root_path(relative));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_remount() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+
+    let mut alloc = Filesystem::allocate();
+    let mut read = [0; 700];
+    let mut prog = [0; 700];
+    let mut lookahead = [0; 16];
+    let buffers = MountBuffers::new(&mut read, &mut prog, &mut lookahead);
+    let fs = Filesystem::mount_with_buffers(&mut alloc, &mut storage, buffers).unwrap();
+    fs.write(path!("file"), b"data").unwrap();
+
+    let fs = fs.remount().unwrap();
+    assert_eq!(fs.read::<4>(path!("file")).unwrap(), b"data");
+    fs.write(path!("other"), b"more").unwrap();
+
+    let (alloc, storage) = fs.unmount().unwrap();
+    let fs = Filesystem::mount(alloc, storage).unwrap();
+    assert_eq!(fs.read::<4>(path!("other")).unwrap(), b"more");
+}
+
+#[test]
+fn test_blocks_used_by() {
+    let mut storage = crate::testing::RamStorage::<256, 64>::new();
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("empty"), b"")?;
+        fs.write(path!("inline"), b"small")?;
+        assert_eq!(fs.blocks_used_by(path!("empty"))?, 0);
+        assert_eq!(fs.blocks_used_by(path!("inline"))?, 0);
+
+        // the first block stores 256 bytes, the others 248 to 252 bytes and one or two pointers
+        for (size, blocks) in [(256, 1), (257, 2), (1000, 4), (2000, 8)] {
+            fs.write(path!("file"), &vec![0x42; size])?;
+            assert_eq!(fs.blocks_used_by(path!("file"))?, blocks, "{size}");
+        }
+
+        let before = fs.available_blocks()?;
+        fs.remove(path!("file"))?;
+        assert_eq!(fs.available_blocks()? - before, 8);
+
+        fs.create_dir(path!("dir"))?;
+        assert_eq!(fs.blocks_used_by(path!("dir")), Err(Error::PATH_IS_DIR));
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_collect_paths() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.create_dir(path!("dir"))?;
+        for path in [path!("dir/a"), path!("dir/b"), path!("dir/c")] {
+            fs.write(path, b"")?;
+        }
+        fs.read_dir_and_then(path!("dir"), |read_dir| {
+            let paths = read_dir.collect_paths::<3>()?;
+            assert_eq!(paths, [path!("dir/a"), path!("dir/b"), path!("dir/c")]);
+            Ok(())
+        })?;
+        fs.read_dir_and_then(path!("dir"), |read_dir| {
+            assert_eq!(read_dir.collect_paths::<2>(), Err(Error::NO_MEMORY));
+            Ok(())
+        })?;
+        fs.read_dir_and_then(path!("dir"), |read_dir| {
+            let paths = read_dir.collect_paths_truncated::<2>()?;
+            assert_eq!(paths, [path!("dir/a"), path!("dir/b")]);
+            let rest = read_dir.collect_paths::<2>()?;
+            assert_eq!(rest, [path!("dir/c")]);
+            Ok(())
+        })
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_open_twice() {
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        for len in [3, 1000] {
+            let old = vec![b'o'; len];
+            fs.write(path!("log"), &old)?;
+
+            let mut writer_alloc = File::allocate();
+            let mut reader_alloc = File::allocate();
+            let writer = unsafe {
+                OpenOptions::new().write(true).append(true).open(
+                    fs,
+                    &mut writer_alloc,
+                    path!("log"),
+                )?
+            };
+            let reader = unsafe { File::open(fs, &mut reader_alloc, path!("log"))? };
+            let mut buf = [0; 2000];
+
+            // the reader keeps seeing the contents at the time it was opened, even after the
+            // writer synced its changes
+            writer.write_all(b"new")?;
+            assert_eq!(reader.len()?, len);
+            writer.sync()?;
+            assert_eq!(reader.len()?, len);
+            assert_eq!(reader.read(&mut buf)?, len);
+            assert_eq!(&buf[..len], old);
+
+            // a file opened after the sync sees the changes
+            fs.open_file_and_then(path!("log"), |file| {
+                assert_eq!(file.len()?, len + 3);
+                Ok(())
+            })?;
+
+            unsafe {
+                writer.close()?;
+                reader.close()?;
+            }
+        }
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_dyn_file() {
+    use crate::object_safe::DynFile;
+
+    fn append_checksum(file: &dyn DynFile) -> Result<u8> {
+        file.seek(SeekFrom::Start(0))?;
+        let mut checksum = 0u8;
+        let mut buf = [0; 4];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            checksum = buf[..n]
+                .iter()
+                .fold(checksum, |sum, byte| sum.wrapping_add(*byte));
+        }
+        file.write_all(&[checksum])?;
+        Ok(checksum)
+    }
+
+    let mut backend = Ram::default();
+    let mut storage = RamStorage::new(&mut backend);
+    Filesystem::format(&mut storage).unwrap();
+    Filesystem::mount_and_then(&mut storage, |fs| {
+        fs.write(path!("file"), &[1, 2, 3, 4, 5])?;
+        fs.open_file_with_options_and_then(
+            |options| options.read(true).write(true),
+            path!("file"),
+            |file| append_checksum(file),
+        )?;
+        assert_eq!(fs.read::<8>(path!("file"))?, [1, 2, 3, 4, 5, 15]);
+        Ok(())
+    })
+    .unwrap();
+}